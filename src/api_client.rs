@@ -2,12 +2,15 @@ use crate::gitlab_api::{GitLabApi, GitLabApiV4};
 
 pub const DEFAULT_ENVIRONMENT: &str = "All";
 
+pub const DEFAULT_URL: &str = "https://gitlab.com";
+
 /// Returns a `GitLabApi` object used for connecting to GitLab API
 ///
 /// # Arguments
 ///
 /// * `url` - GitLab instance URL
-/// * `token` - Token used to connect to GitLab API
+/// * `token` - Token used to connect to GitLab API. Job and OAuth2 tokens are detected from `CI_JOB_TOKEN` and
+///   `GITLAB_OAUTH_TOKEN` variables.
 ///
 /// # Example
 ///
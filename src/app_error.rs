@@ -1,4 +1,5 @@
 use ansi_term::Colour::Red;
+use reqwest::StatusCode;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
@@ -7,6 +8,7 @@ use std::fmt::{Display, Formatter, Result as FmtResult};
 pub enum AppError {
     InvalidInput(String),
     Api(String),
+    NotFound(String),
     Cli(String),
 }
 
@@ -14,7 +16,10 @@ impl Error for AppError {}
 
 impl From<reqwest::Error> for AppError {
     fn from(e: reqwest::Error) -> AppError {
-        AppError::Api(format!("{}", e))
+        match e.status() {
+            Some(StatusCode::NOT_FOUND) => AppError::NotFound(format!("{}", e)),
+            _ => AppError::Api(format!("{}", e)),
+        }
     }
 }
 
@@ -29,6 +34,7 @@ impl Display for AppError {
         match self {
             AppError::InvalidInput(e) => write!(f, "{} {}", Red.bold().paint("[InvalidInputError]"), e),
             AppError::Api(e) => write!(f, "{} {}", Red.bold().paint("[ApiError]"), e),
+            AppError::NotFound(e) => write!(f, "{} {}", Red.bold().paint("[NotFoundError]"), e),
             AppError::Cli(e) => write!(f, "{} {}", Red.bold().paint("[CliError]"), e),
         }
     }
@@ -38,6 +44,7 @@ impl Display for AppError {
 pub type Result<T> = std::result::Result<T, AppError>;
 
 #[cfg(test)]
+#[allow(clippy::io_other_error)]
 mod tests {
     use super::*;
     use reqwest::blocking::Client as BlockingClient;
@@ -70,6 +77,14 @@ mod tests {
         assert_eq!(format!("{}", AppError::Api("An error".to_owned())), format!("{} An error", Red.bold().paint("[ApiError]")));
     }
 
+    #[test]
+    fn test_not_found_error_display() {
+        assert_eq!(
+            format!("{}", AppError::NotFound("An error".to_owned())),
+            format!("{} An error", Red.bold().paint("[NotFoundError]"))
+        );
+    }
+
     #[test]
     fn test_cli_error_display() {
         assert_eq!(format!("{}", AppError::Cli("An error".to_owned())), format!("{} An error", Red.bold().paint("[CliError]")));
@@ -0,0 +1,285 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::api_client;
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::config::Profile;
+use crate::dotenv::{all_from_api, all_pages_from_api, RequestConfig};
+use crate::gitlab_api::{GitLabApi, GitLabGroup, GitLabProjectSummary, GitLabVariable, VariableSource};
+use crate::io::IO;
+use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::convert::From;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use urlencoding::encode;
+
+/// Version of the backup archive format written by `backup` command
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Backup of all CI/CD variables of a group tree
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BackupArchive {
+    /// Version of the archive format
+    pub version: u32,
+    /// Full path of the group the backup was taken from
+    pub root_group: String,
+    /// Variables of the root group and all its subgroups
+    pub groups: Vec<BackupEntry>,
+    /// Variables of all projects of the group tree
+    pub projects: Vec<BackupEntry>,
+}
+
+/// Variables of a single group or project
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BackupEntry {
+    /// Full path of the group or project
+    pub path: String,
+    /// Variables of the group or project
+    pub variables: Vec<GitLabVariable>,
+}
+
+/// Arguments for `backup` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackupCommand {
+    /// Group ID or path of the root group
+    group: String,
+    /// Path of the archive file
+    file: String,
+    /// Parallelism
+    parallel: usize,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+impl Performable for BackupCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            let archive = backup_group_tree(&self)?;
+            let mut file = open_archive_file(&self.file).map_err(|e| InvalidInput(format!("File {} could not be created. Error: {}", self.file, e)))?;
+            file.write_all(serde_json::to_string_pretty(&archive).map_err(|e| InvalidInput(format!("{}", e)))?.as_bytes())?;
+            Ok(app_success!(
+                "{} variable(s) of {} group(s) and {} project(s) saved in {}",
+                archive.groups.iter().chain(archive.projects.iter()).map(|e| e.variables.len()).sum::<usize>(),
+                archive.groups.len(),
+                archive.projects.len(),
+                self.file
+            ))
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for BackupCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        BackupCommand {
+            group: argm.value_of("GITLAB_GROUP").unwrap().to_owned(),
+            file: argm.value_of("FILE").unwrap().to_owned(),
+            parallel: argm.value_of("parallel").and_then(|v| v.parse::<usize>().ok()).unwrap_or_else(num_cpus::get),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Returns an archive with the variables of a group, all its subgroups and all their projects
+///
+/// # Arguments
+///
+/// * `cmd` - Backup command
+///
+fn backup_group_tree(cmd: &BackupCommand) -> Result<BackupArchive> {
+    app_info!("Getting group {}...", cmd.group);
+    let root = api_client(&cmd.url, &cmd.token).get_group(&encode(&cmd.group))?;
+    let groups = group_tree(cmd, root.clone())?;
+    let projects = groups.iter().try_fold(vec![], |acc: Vec<GitLabProjectSummary>, g| -> Result<Vec<GitLabProjectSummary>> {
+        Ok([
+            acc,
+            all_pages_from_api(request(cmd, VariableSource::Group(g.id.to_string())), cmd.parallel, list_projects_from_api)?,
+        ]
+        .concat())
+    })?;
+    Ok(BackupArchive {
+        version: ARCHIVE_VERSION,
+        root_group: root.full_path,
+        groups: groups
+            .into_iter()
+            .map(|g| backup_entry(cmd, g.full_path, VariableSource::Group(g.id.to_string())))
+            .collect::<Result<Vec<BackupEntry>>>()?,
+        projects: projects
+            .into_iter()
+            .map(|p| backup_entry(cmd, p.path_with_namespace, VariableSource::Project(p.id.to_string())))
+            .collect::<Result<Vec<BackupEntry>>>()?,
+    })
+}
+
+/// Returns a group followed by all its descendant groups
+///
+/// # Arguments
+///
+/// * `cmd`   - Backup command
+/// * `group` - Group to walk
+///
+fn group_tree(cmd: &BackupCommand, group: GitLabGroup) -> Result<Vec<GitLabGroup>> {
+    all_pages_from_api(request(cmd, VariableSource::Group(group.id.to_string())), cmd.parallel, list_subgroups_from_api)?
+        .into_iter()
+        .try_fold(vec![group], |acc, subgroup| -> Result<Vec<GitLabGroup>> { Ok([acc, group_tree(cmd, subgroup)?].concat()) })
+}
+
+/// Returns all variables of a group or project
+///
+/// # Arguments
+///
+/// * `cmd`    - Backup command
+/// * `path`   - Full path of the group or project
+/// * `source` - Group or project to request variables from
+///
+fn backup_entry(cmd: &BackupCommand, path: String, source: VariableSource) -> Result<BackupEntry> {
+    app_info!("Getting variables from {}...", path);
+    Ok(BackupEntry {
+        variables: all_from_api(request(cmd, source), cmd.parallel)?,
+        path,
+    })
+}
+
+/// Returns request parameters for the first page of a list
+fn request(cmd: &BackupCommand, source: VariableSource) -> RequestConfig {
+    RequestConfig::new(&cmd.url, &cmd.token, source, 1, 100)
+}
+
+/// Returns a page of subgroups of the group in `request`
+fn list_subgroups_from_api(request: RequestConfig) -> Result<(Vec<GitLabGroup>, usize)> {
+    match &request.source {
+        VariableSource::Group(g) => api_client(&request.url, &request.token).list_subgroups(g, request.page, request.per_page),
+        _ => Ok((vec![], 0)),
+    }
+}
+
+/// Returns a page of projects of the group in `request`
+fn list_projects_from_api(request: RequestConfig) -> Result<(Vec<GitLabProjectSummary>, usize)> {
+    match &request.source {
+        VariableSource::Group(g) => api_client(&request.url, &request.token).list_group_projects(g, request.page, request.per_page),
+        _ => Ok((vec![], 0)),
+    }
+}
+
+/// Creates the archive file, readable only by its owner on Unix systems since it contains secrets. Permissions of an
+/// existing file are restricted as well before it is overwritten.
+///
+/// # Arguments
+///
+/// * `file` - Path of the archive file
+///
+fn open_archive_file(file: &str) -> std::io::Result<std::fs::File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(file)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+    use httpmock::MockServer;
+
+    #[test]
+    fn test_should_create_backup_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "backup",
+                "my-group",
+                "backup.json",
+                "--parallel=2",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("backup")
+            .map(|a| {
+                assert_eq!(
+                    BackupCommand::from(a),
+                    BackupCommand {
+                        group: "my-group".to_owned(),
+                        file: "backup.json".to_owned(),
+                        parallel: 2,
+                        url: "gitlab.com".to_owned(),
+                        token: GEN_TOKEN.clone(),
+                    }
+                )
+            })
+            .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_archive_file_restricts_permissions_of_existing_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let file = format!("{}/archive-{}.json", env::temp_dir().display(), *GEN_NAME);
+        std::fs::write(&file, "old").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        open_archive_file(&file).unwrap();
+        let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_should_backup_group_tree() {
+        let (root, subgroup) = (
+            GitLabGroup {
+                id: 1,
+                full_path: "root".to_owned(),
+            },
+            GitLabGroup {
+                id: 2,
+                full_path: "root/sub".to_owned(),
+            },
+        );
+        let project = GitLabProjectSummary {
+            id: 3,
+            path_with_namespace: "root/sub/app".to_owned(),
+        };
+        let (root_variables, subgroup_variables, project_variables) = (gen_variable_list(2), gen_variable_list(1), gen_variable_list(3));
+        let server = MockServer::start();
+        let mocks = [
+            server.mock(httpmock_group(root.clone())),
+            server.mock(httpmock_list_subgroups(1, vec![subgroup.clone()])),
+            server.mock(httpmock_list_subgroups(2, vec![])),
+            server.mock(httpmock_list_group_projects(1, vec![])),
+            server.mock(httpmock_list_group_projects(2, vec![project])),
+            server.mock(httpmock_list_group_variables("1".to_owned(), root_variables.clone())),
+            server.mock(httpmock_list_group_variables("2".to_owned(), subgroup_variables.clone())),
+            server.mock(|when, then| {
+                when.method("GET").path("/api/v4/projects/3/variables");
+                then.status(200)
+                    .header("Content-Type", "application/json")
+                    .header("x-total", &project_variables.len().to_string())
+                    .json_body_obj(&project_variables);
+            }),
+        ];
+        let cmd = BackupCommand {
+            group: "root".to_owned(),
+            file: "backup.json".to_owned(),
+            parallel: 1,
+            url: server.base_url(),
+            token: GEN_TOKEN.clone(),
+        };
+        let archive = backup_group_tree(&cmd).unwrap();
+        assert_eq!((archive.version, archive.root_group.as_str()), (ARCHIVE_VERSION, "root"),);
+        assert_eq!(
+            archive.groups.iter().map(|e| (e.path.as_str(), e.variables.len())).collect::<Vec<(&str, usize)>>(),
+            vec![("root", root_variables.len()), ("root/sub", subgroup_variables.len())]
+        );
+        assert_eq!(archive.projects.iter().map(|e| e.path.as_str()).collect::<Vec<&str>>(), vec!["root/sub/app"]);
+        mocks.iter().for_each(|m| m.assert());
+    }
+}
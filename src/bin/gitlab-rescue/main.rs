@@ -1,21 +1,58 @@
-use gitlab_rescue::app_error::AppError::InvalidInput;
+use clap::ArgMatches;
+use gitlab_rescue::app_error::{AppError::InvalidInput, Result};
+use gitlab_rescue::backup::BackupCommand;
 use gitlab_rescue::clap_app::app;
+use gitlab_rescue::compare::CompareCommand;
+use gitlab_rescue::config::{check_profile, defaults};
+use gitlab_rescue::copy::CopyCommand;
+use gitlab_rescue::delete_variable::DeleteVariableCommand;
+use gitlab_rescue::diff::DiffCommand;
 use gitlab_rescue::dotenv::DotEnvCommand;
+use gitlab_rescue::exec::ExecCommand;
 use gitlab_rescue::get_variable::GetVariableCommand;
+use gitlab_rescue::import::ImportCommand;
 use gitlab_rescue::io::IO;
+use gitlab_rescue::restore::RestoreCommand;
+use gitlab_rescue::set_variable::SetVariableCommand;
+use gitlab_rescue::sync::SyncCommand;
+use gitlab_rescue::token::check_token;
 use gitlab_rescue::Performable;
 use std::process;
 
 /// Main action that applies the effect returned by command
 fn main() {
-    match app().get_matches().subcommand() {
+    let matches = app().get_matches();
+    let (command, args) = matches.subcommand();
+    args.map_or(Ok(()), |a| defaults(a).and_then(|p| check_profile(command, a, &p).and_then(|_| check_token(a, &p))))
+        .map_or_else(|e| IO::unit(move || Err(e)), |_| action(command, args))
+        .apply()
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+}
+
+/// Returns the action of a command
+///
+/// # Arguments
+///
+/// * `command` - Name of the subcommand
+/// * `args`    - Arguments of the subcommand
+///
+fn action(command: &str, args: Option<&ArgMatches>) -> IO<Result<()>> {
+    match (command, args) {
         ("get", Some(args)) => GetVariableCommand::from(args).get_action(),
+        ("set", Some(args)) => SetVariableCommand::from(args).get_action(),
+        ("delete", Some(args)) => DeleteVariableCommand::from(args).get_action(),
+        ("import", Some(args)) => ImportCommand::from(args).get_action(),
+        ("compare", Some(args)) => CompareCommand::from(args).get_action(),
+        ("backup", Some(args)) => BackupCommand::from(args).get_action(),
+        ("restore", Some(args)) => RestoreCommand::from(args).get_action(),
+        ("sync", Some(args)) => SyncCommand::from(args).get_action(),
+        ("copy", Some(args)) => CopyCommand::from(args).get_action(),
+        ("diff", Some(args)) => DiffCommand::from(args).get_action(),
         ("dotenv", Some(args)) => DotEnvCommand::from(args).get_action(),
+        ("exec", Some(args)) => ExecCommand::from(args).get_action(),
         _ => IO::unit(|| Err(InvalidInput("Command is not valid. For more information try --help.".to_owned()))),
     }
-    .apply()
-    .unwrap_or_else(|e| {
-        eprintln!("{}", e);
-        process::exit(1);
-    });
 }
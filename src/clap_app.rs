@@ -6,23 +6,43 @@ fn environment_arg() -> Arg<'static, 'static> {
         .long("environment")
         .short("e")
         .value_name("ENVIRONMENT")
-        .long_help("Name of GitLab CI/CD environment.")
-        .default_value("All")
+        .long_help("Name of GitLab CI/CD environment. [default: environment of the repository or the profile, or All]")
 }
 
-/// Returns an array with `--token` and `--url` flags configuration
-fn gitlab_instance_args() -> [Arg<'static, 'static>; 2] {
+/// Returns an arg object with `--environment` flag configuration for commands that write variables. The default
+/// environment of the repository or the profile is not used, so variables are only written in a scope other than
+/// "All" when it is explicitly requested.
+fn write_environment_arg() -> Arg<'static, 'static> {
+    environment_arg().long_help("Name of GitLab CI/CD environment.").default_value("All")
+}
+
+/// Returns an array with `--token`, `--token-file`, `--token-command`, `--url` and `--profile` flags configuration
+fn gitlab_instance_args() -> [Arg<'static, 'static>; 5] {
     [
         Arg::with_name("token")
             .long("token")
             .short("t")
             .value_name("GITLAB_API_TOKEN")
-            .long_help("A valid GitLab API token. Alternatively, you can export GITLAB_API_TOKEN variable."),
+            .long_help("A valid GitLab API token. It is visible in shell history and process listings, so prefer --token-file or --token-command options. Alternatively, you can export GITLAB_API_TOKEN variable, set it in a profile, or add it to ~/.netrc or to glab's configuration."),
+        Arg::with_name("token-file")
+            .long("token-file")
+            .value_name("PATH")
+            .conflicts_with_all(&["token", "token-command"])
+            .long_help("File that holds the GitLab API token."),
+        Arg::with_name("token-command")
+            .long("token-command")
+            .value_name("COMMAND")
+            .conflicts_with("token")
+            .long_help("Command that prints the GitLab API token, run by the shell (e.g. \"pass show gitlab\")."),
         Arg::with_name("url")
             .long("url")
             .short("u")
             .value_name("GITLAB_URL")
-            .long_help("URL of GitLab API. [default: https://gitlab.com]. Alternatively, you can export GITLAB_URL variable."),
+            .long_help("URL of GitLab API. [default: https://gitlab.com]. Alternatively, you can export GITLAB_URL variable or set it in the repository or a profile."),
+        Arg::with_name("profile")
+            .long("profile")
+            .value_name("PROFILE")
+            .long_help("Profile of the configuration file with default values for options. Alternatively, you can export GITLAB_RESCUE_PROFILE variable."),
     ]
 }
 
@@ -33,25 +53,117 @@ fn project_and_group_args() -> [Arg<'static, 'static>; 2] {
             .long("project")
             .short("p")
             .value_name("GITLAB_PROJECT")
-            .long_help("The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project. This should not be used with --group option.")
-            .conflicts_with("group")
-            .required(true),
+            .long_help(
+                "The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project. This should not be used with --group option. [default: project of the repository, or project or group of the profile]",
+            )
+            .conflicts_with_all(&["group", "instance"]),
         Arg::with_name("group")
             .long("group")
             .short("g")
             .value_name("GITLAB_GROUP")
             .long_help("The ID of a group or URL-encoded path of the group. This should not be used with --project option.")
-            .conflicts_with("project")
-            .required(true),
+            .conflicts_with_all(&["project", "instance"]),
+    ]
+}
+
+/// Returns an arg object with `--instance` flag configuration
+fn instance_arg() -> Arg<'static, 'static> {
+    Arg::with_name("instance")
+        .long("instance")
+        .long_help("Use instance-level variables (self-managed GitLab only). This should not be used with --project or --group options.")
+        .conflicts_with_all(&["project", "group"])
+}
+
+/// Returns an arg object with `--type` option configuration
+fn type_arg() -> Arg<'static, 'static> {
+    Arg::with_name("type")
+        .long("type")
+        .value_name("TYPE")
+        .possible_values(&["env_var", "file"])
+        .long_help("The type of the variable. If not defined, GitLab default (env_var) is used for new variables and current type is kept for existing ones.")
+}
+
+/// Returns an arg object with `--protected` option configuration
+fn protected_arg() -> Arg<'static, 'static> {
+    Arg::with_name("protected")
+        .long("protected")
+        .value_name("BOOL")
+        .possible_values(&["true", "false"])
+        .long_help("Whether the variable is only exported in pipelines running on protected branches and tags.")
+}
+
+/// Returns an arg object with `--masked` option configuration
+fn masked_arg() -> Arg<'static, 'static> {
+    Arg::with_name("masked")
+        .long("masked")
+        .value_name("BOOL")
+        .possible_values(&["true", "false"])
+        .long_help("Whether the value of the variable is masked in job logs.")
+}
+
+/// Returns an arg object with `--raw` option configuration
+fn raw_arg() -> Arg<'static, 'static> {
+    Arg::with_name("raw")
+        .long("raw")
+        .value_name("BOOL")
+        .possible_values(&["true", "false"])
+        .long_help("Whether the variable is treated as a raw string, so variable references in its value are not expanded.")
+}
+
+/// Returns an array with `--type`, `--protected`, `--masked` and `--raw` options configuration
+fn variable_attributes_args() -> [Arg<'static, 'static>; 4] {
+    [type_arg(), protected_arg(), masked_arg(), raw_arg()]
+}
+
+/// Returns an arg object with `--parallel` option configuration
+fn parallel_arg() -> Arg<'static, 'static> {
+    Arg::with_name("parallel")
+        .long("parallel")
+        .value_name("PARALLEL")
+        .long_help("Number of threads for GitLab API requests.")
+}
+
+/// Returns an array with options for listing the variables of a project (pagination, parallelism and inherited variables)
+fn list_variables_args() -> [Arg<'static, 'static>; 7] {
+    [
+        Arg::with_name("per-page")
+            .long("per-page")
+            .value_name("PER_PAGE")
+            .long_help("Number of items to bring per request.\r\n(See https://docs.gitlab.com/ee/api/README.html#offset-based-pagination).")
+            .default_value("100"),
+        parallel_arg(),
+        Arg::with_name("with-group-vars").long("with-group-vars").long_help(
+            "Include variables inherited from the project's group and all its parent groups. Project variables take precedence over group variables, and child groups over parent groups.",
+        ),
+        Arg::with_name("with-instance-vars")
+            .long("with-instance-vars")
+            .long_help("Include instance-level variables (self-managed GitLab only). Instance variables have the lowest precedence."),
+        Arg::with_name("only-protected")
+            .long("only-protected")
+            .long_help("Only include protected variables.")
+            .conflicts_with("exclude-protected"),
+        Arg::with_name("exclude-protected")
+            .long("exclude-protected")
+            .long_help("Exclude protected variables, as GitLab does in jobs of unprotected branches and tags.")
+            .conflicts_with("only-protected"),
+        Arg::with_name("exclude-masked").long("exclude-masked").long_help("Exclude masked variables."),
     ]
 }
 
+/// Validates that a label has format KEY=VALUE
+fn is_label(label: String) -> Result<(), String> {
+    match label.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(()),
+        _ => Err(format!("Label {} must have format KEY=VALUE", label)),
+    }
+}
+
 /// Returns the `ClapApp` object with all CLI structure
 pub fn app() -> ClapApp<'static, 'static> {
     ClapApp::new("gitlab-rescue")
         .version(crate_version!())
         .author(crate_authors!())
-        .about("CLI tool for getting and importing GitLab CI/CD variables from a project (Read only)")
+        .about("CLI tool for getting and importing GitLab CI/CD variables from a project")
         .subcommand(
             // Get command
             SubCommand::with_name("get")
@@ -59,8 +171,9 @@ pub fn app() -> ClapApp<'static, 'static> {
                 .author(crate_authors!())
                 .about("Print variable in STDOUT")
                 .args(&gitlab_instance_args())
-                .arg(&environment_arg())
+                .arg(environment_arg())
                 .args(&project_and_group_args())
+                .arg(instance_arg())
                 .args(&[
                     Arg::with_name("VARIABLE_NAME").long_help("Name of GitLab CI/CD variable.").required(true).index(1),
                     Arg::with_name("from-all-if-missing")
@@ -68,6 +181,230 @@ pub fn app() -> ClapApp<'static, 'static> {
                         .long_help("If variable(s) is(are) not found in defined environment (-e option), try searching in \"All\" environment."),
                 ]),
         )
+        .subcommand(
+            // Set command
+            SubCommand::with_name("set")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Create a variable or update it in place")
+                .args(&gitlab_instance_args())
+                .arg(write_environment_arg())
+                .args(&project_and_group_args())
+                .args(&variable_attributes_args())
+                .args(&[
+                    Arg::with_name("VARIABLE_NAME").long_help("Name of GitLab CI/CD variable.").required(true).index(1),
+                    Arg::with_name("VALUE")
+                        .long_help("Value of GitLab CI/CD variable. Use - to read it from STDIN, so it is not kept in shell history.")
+                        .required_unless("value-file")
+                        .index(2),
+                    Arg::with_name("value-file")
+                        .long("value-file")
+                        .value_name("FILE")
+                        .conflicts_with("VALUE")
+                        .long_help("Read the value of the variable from this file instead of VALUE."),
+                    Arg::with_name("description")
+                        .long("description")
+                        .value_name("DESCRIPTION")
+                        .long_help("Description of the variable. If not present, current description is kept on update."),
+                ]),
+        )
+        .subcommand(
+            // Delete command
+            SubCommand::with_name("delete")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Delete a variable from a specific environment scope")
+                .args(&gitlab_instance_args())
+                .arg(write_environment_arg())
+                .args(&project_and_group_args())
+                .args(&[
+                    Arg::with_name("VARIABLE_NAME").long_help("Name of GitLab CI/CD variable.").required(true).index(1),
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .long_help("Delete the variable without asking for confirmation."),
+                ]),
+        )
+        .subcommand(
+            // Import command
+            SubCommand::with_name("import")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Create or update variables defined in a dotenv file")
+                .args(&gitlab_instance_args())
+                .arg(write_environment_arg())
+                .args(&project_and_group_args())
+                .args(&[protected_arg(), masked_arg(), raw_arg()])
+                .args(&[
+                    Arg::with_name("FILE")
+                        .long_help("Dotenv file with POSIX (export KEY=\"value\"), fish (set -gx KEY \"value\") or plain (KEY=value) statements.")
+                        .required(true)
+                        .index(1),
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .long_help("Print the change that would be applied to each variable without applying it."),
+                ]),
+        )
+        .subcommand(
+            // Diff command
+            SubCommand::with_name("diff")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Show differences between project variables and a local dotenv file")
+                .args(&gitlab_instance_args())
+                .arg(environment_arg())
+                .args(&[
+                    Arg::with_name("GITLAB_PROJECT")
+                        .long_help("The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project. [default: project of the repository or the profile]")
+                        .required_unless("FILE")
+                        .index(1),
+                    Arg::with_name("FILE")
+                        .long_help("Dotenv file with POSIX (export KEY=\"value\"), fish (set -gx KEY \"value\") or plain (KEY=value) statements. When it is the only positional argument, variables of the default project are compared.")
+                        .index(2),
+                    Arg::with_name("show-values")
+                        .long("show-values")
+                        .long_help("Print values of variables. By default, values are masked."),
+                ])
+                .args(&list_variables_args()),
+        )
+        .subcommand(
+            // Compare command
+            SubCommand::with_name("compare")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Show differences between variables of two projects or environments")
+                .args(&gitlab_instance_args())
+                .arg(environment_arg())
+                .args(&[
+                    Arg::with_name("GITLAB_PROJECT")
+                        .long_help("The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project. [default: project of the repository or the profile]")
+                        .index(1),
+                    Arg::with_name("to-project")
+                        .long("to-project")
+                        .value_name("GITLAB_PROJECT")
+                        .long_help("Project to compare with. [default: <GITLAB_PROJECT>]"),
+                    Arg::with_name("to-environment")
+                        .long("to-environment")
+                        .value_name("ENVIRONMENT")
+                        .long_help("Name of GitLab CI/CD environment to compare with. [default: <ENVIRONMENT>]"),
+                    Arg::with_name("show-values")
+                        .long("show-values")
+                        .long_help("Print values of variables. By default, values are masked."),
+                ])
+                .args(&list_variables_args()),
+        )
+        .subcommand(
+            // Copy command
+            SubCommand::with_name("copy")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Copy variables from a project or group to another one")
+                .args(&gitlab_instance_args())
+                .args(&project_and_group_args())
+                .args(&[
+                    Arg::with_name("environment")
+                        .long("environment")
+                        .short("e")
+                        .value_name("ENVIRONMENT")
+                        .long_help("Only copy variables defined in this environment scope. [default: all scopes]"),
+                    Arg::with_name("to-project")
+                        .long("to-project")
+                        .value_name("GITLAB_PROJECT")
+                        .long_help("The ID of a project or NAMESPACE/PROJECT_NAME of the destination project. This should not be used with --to-group option.")
+                        .conflicts_with("to-group")
+                        .required_unless("to-group"),
+                    Arg::with_name("to-group")
+                        .long("to-group")
+                        .value_name("GITLAB_GROUP")
+                        .long_help("The ID of a group or path of the destination group. This should not be used with --to-project option.")
+                        .conflicts_with("to-project")
+                        .required_unless("to-project"),
+                    Arg::with_name("to-environment")
+                        .long("to-environment")
+                        .value_name("ENVIRONMENT")
+                        .long_help("Environment scope of copied variables. [default: original scope]"),
+                    Arg::with_name("filter")
+                        .long("filter")
+                        .value_name("GLOB")
+                        .long_help("Only copy variables whose key matches this pattern, where * matches any sequence of characters (e.g. DB_*)."),
+                    Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .long_help("Overwrite variables that already exist in destination. By default, they are skipped."),
+                ]),
+        )
+        .subcommand(
+            // Backup command
+            SubCommand::with_name("backup")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Save variables of a group, its subgroups and all their projects in an archive file")
+                .args(&gitlab_instance_args())
+                .args(&[
+                    Arg::with_name("GITLAB_GROUP")
+                        .long_help("The ID of a group or path of the root group.")
+                        .required(true)
+                        .index(1),
+                    Arg::with_name("FILE").long_help("Path of the archive file to create.").required(true).index(2),
+                    parallel_arg(),
+                ]),
+        )
+        .subcommand(
+            // Restore command
+            SubCommand::with_name("restore")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Recreate variables saved in an archive file by backup command")
+                .args(&gitlab_instance_args())
+                .args(&[
+                    Arg::with_name("FILE")
+                        .long_help("Path of the archive file created by backup command.")
+                        .required(true)
+                        .index(1),
+                    Arg::with_name("to-group")
+                        .long("to-group")
+                        .value_name("GITLAB_GROUP")
+                        .long_help("Path of the group that replaces the original root group. Subgroups and projects must already exist under it. [default: original root group]"),
+                    parallel_arg(),
+                ]),
+        )
+        .subcommand(
+            // Sync command
+            SubCommand::with_name("sync")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Reconcile variables of a project or group with a YAML or TOML manifest")
+                .args(&gitlab_instance_args())
+                .args(&[
+                    Arg::with_name("MANIFEST")
+                        .long_help("YAML (.yaml, .yml) or TOML (.toml) file declaring the project or group and its variables.")
+                        .required(true)
+                        .index(1),
+                    Arg::with_name("prune").long("prune").long_help("Delete variables that are not declared in the manifest."),
+                    Arg::with_name("apply").long("apply").long_help("Execute the plan. By default, changes are only printed."),
+                ]),
+        )
+        .subcommand(
+            // Exec command
+            SubCommand::with_name("exec")
+                .version(crate_version!())
+                .author(crate_authors!())
+                .about("Run a command with project variables in its environment")
+                .args(&gitlab_instance_args())
+                .arg(environment_arg())
+                .args(&[
+                    Arg::with_name("GITLAB_PROJECT")
+                        .long("project")
+                        .short("p")
+                        .value_name("GITLAB_PROJECT")
+                        .long_help("The ID of a project or NAMESPACE/PROJECT_NAME of the project. [default: project of the repository or the profile]"),
+                    Arg::with_name("COMMAND")
+                        .long_help("Command to run and its arguments, after --. Variables with type \"File\" are written to a private temporary folder, which is removed when the command exits.")
+                        .multiple(true)
+                        .required(true)
+                        .last(true),
+                ])
+                .args(&list_variables_args()),
+        )
         .subcommand(
             // Local Env command
             SubCommand::with_name("dotenv")
@@ -76,12 +413,11 @@ pub fn app() -> ClapApp<'static, 'static> {
                 .about("Export project variables in the current shell (by default first 20 variables)")
                 .arg(
                     Arg::with_name("GITLAB_PROJECT")
-                        .long_help("The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project.")
-                        .required(true)
+                        .long_help("The ID of a project or URL-encoded NAMESPACE/PROJECT_NAME of the project. [default: project of the repository or the profile]")
                         .index(1),
                 )
                 .args(&gitlab_instance_args())
-                .arg(&environment_arg())
+                .arg(environment_arg())
                 .args(&[
                     Arg::with_name("output")
                         .long("output")
@@ -92,26 +428,44 @@ pub fn app() -> ClapApp<'static, 'static> {
                         .long("shell")
                         .short("s")
                         .value_name("SHELL")
-                        .possible_values(&["bash", "zsh", "fish"])
-                        .default_value("bash")
-                        .long_help("Generate dotenv for this shell type. Supported shells are: bash, zsh and fish."),
+                        .possible_values(&["bash", "zsh", "fish", "powershell", "pwsh", "cmd", "nushell", "nu"])
+                        .long_help("Generate dotenv for this shell type. Supported shells are: bash, zsh, fish, powershell (pwsh), cmd and nushell (nu). [default: shell of the repository or the profile, or bash]"),
+                    Arg::with_name("format")
+                        .long("format")
+                        .short("f")
+                        .value_name("FORMAT")
+                        .possible_values(&["shell", "dotenv", "json", "yaml", "docker", "secret", "configmap", "systemd", "direnv"])
+                        .default_value("shell")
+                        .long_help(
+                            "Output format. \"shell\" generates commands for the shell of --shell option, \"dotenv\" generates plain KEY=VALUE lines (as read by docker-compose and python-dotenv), \"json\" and \"yaml\" generate a list of variables with their scope, type, flags and description, \"docker\" generates an env-file for docker run --env-file, \"secret\" and \"configmap\" generate a Kubernetes manifest, \"systemd\" generates a file for systemd EnvironmentFile= directive, and \"direnv\" generates an .envrc file that loads variables with gitlab-rescue each time direnv loads it.",
+                        ),
+                    Arg::with_name("k8s-name").long("k8s-name").value_name("NAME").long_help(
+                        "Name of the Kubernetes Secret or ConfigMap. [default: <PROJECT_NAME>-<ENVIRONMENT>]",
+                    ),
+                    Arg::with_name("k8s-namespace")
+                        .long("k8s-namespace")
+                        .value_name("NAMESPACE")
+                        .long_help("Namespace of the Kubernetes Secret or ConfigMap."),
+                    Arg::with_name("k8s-label")
+                        .long("k8s-label")
+                        .value_name("KEY=VALUE")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(is_label)
+                        .long_help("Label of the Kubernetes Secret or ConfigMap. This option can be repeated."),
+                    Arg::with_name("container-folder").long("container-folder").value_name("PATH").long_help(
+                        "Path where the folder of variables with type \"File\" (--folder) is mounted inside a container. Paths of these variables point to it in all formats except \"shell\".",
+                    ),
                     Arg::with_name("folder").long("folder").value_name("PATH").long_help(
-                        "Path where variables with type \"File\" will be stored. Files will be created with format <VARIABLE_NAME>.var. [default: $PWD/.env.<ENVIRONMENT>]",
+                        "Path where variables with type \"File\" will be stored. Files will be created with format <VARIABLE_NAME>.var, only in \"shell\" format and in \"dotenv\" format without --container-folder. [default: $PWD/.env.<ENVIRONMENT>]",
                     ),
-                    Arg::with_name("per-page")
-                        .long("per-page")
-                        .value_name("PER_PAGE")
-                        .long_help("Number of items to bring per request.\r\n(See https://docs.gitlab.com/ee/api/README.html#offset-based-pagination).")
-                        .default_value("100"),
-                    Arg::with_name("parallel")
-                        .long("parallel")
-                        .value_name("PARALLEL")
-                        .long_help("Number of threads for GitLab API requests."),
-                ]),
+                ])
+                .args(&list_variables_args()),
         )
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_to_owned)]
 mod tests {
     use super::*;
 
@@ -124,6 +478,15 @@ mod tests {
             .map_or_else(|| panic!(), |v| assert_eq!(v, "production"));
     }
 
+    #[test]
+    fn test_write_environment_arg() {
+        ClapApp::new("gitlab-rescue")
+            .arg(write_environment_arg())
+            .get_matches_from(vec!["gitlab-rescue"])
+            .value_of("environment")
+            .map_or_else(|| panic!(), |v| assert_eq!(v, "All"));
+    }
+
     #[test]
     fn test_gitlab_instance_token_arg() {
         ClapApp::new("gitlab-rescue")
@@ -178,6 +541,123 @@ mod tests {
             .map_or_else(|| panic!(), |v| assert_eq!(v, "MY_VARIABLE"));
     }
 
+    #[test]
+    fn test_get_instance_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "get", "MY_VARIABLE", "--instance"])
+            .subcommand_matches("get")
+            .map_or_else(|| panic!(), |args| assert!(args.is_present("instance")));
+    }
+
+    #[test]
+    fn test_instance_arg_conflicts_with_project() {
+        assert!(app()
+            .get_matches_from_safe(vec!["gitlab-rescue", "get", "MY_VARIABLE", "--instance", "-p", "project"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "set", "MY_VARIABLE", "a-value", "-p", "project", "--masked", "true"])
+            .subcommand_matches("set")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("VALUE"), args.value_of("masked")), (Some("a-value"), Some("true"))),
+            );
+    }
+
+    #[test]
+    fn test_set_command_value_file() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "set", "MY_VARIABLE", "--value-file", "secret.txt", "-p", "project"])
+            .subcommand_matches("set")
+            .map_or_else(|| panic!(), |args| assert_eq!(args.value_of("value-file"), Some("secret.txt")));
+        assert!(app()
+            .get_matches_from_safe(vec!["gitlab-rescue", "set", "MY_VARIABLE", "a-value", "--value-file", "secret.txt"])
+            .is_err());
+        assert!(app().get_matches_from_safe(vec!["gitlab-rescue", "set", "MY_VARIABLE"]).is_err());
+    }
+
+    #[test]
+    fn test_delete_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "delete", "MY_VARIABLE", "-g", "group", "-e", "review/*", "-y"])
+            .subcommand_matches("delete")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("environment"), args.is_present("yes")), (Some("review/*"), true)),
+            );
+    }
+
+    #[test]
+    fn test_import_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "import", ".env", "-p", "project", "--dry-run"])
+            .subcommand_matches("import")
+            .map_or_else(|| panic!(), |args| assert_eq!((args.value_of("FILE"), args.is_present("dry-run")), (Some(".env"), true)));
+    }
+
+    #[test]
+    fn test_diff_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "diff", "a-project", ".env", "--show-values"])
+            .subcommand_matches("diff")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("FILE"), args.is_present("show-values")), (Some(".env"), true)),
+            );
+    }
+
+    #[test]
+    fn test_compare_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "compare", "a-project", "-e", "staging", "--to-environment", "production"])
+            .subcommand_matches("compare")
+            .map_or_else(|| panic!(), |args| assert_eq!(args.value_of("to-environment"), Some("production")));
+    }
+
+    #[test]
+    fn test_copy_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "copy", "-p", "template", "--to-project", "new-service"])
+            .subcommand_matches("copy")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("to-project"), args.value_of("environment")), (Some("new-service"), None)),
+            );
+        assert!(app().get_matches_from_safe(vec!["gitlab-rescue", "copy", "-p", "template"]).is_err());
+    }
+
+    #[test]
+    fn test_backup_and_restore_commands() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "backup", "a-group", "backup.json"])
+            .subcommand_matches("backup")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("GITLAB_GROUP"), args.value_of("FILE")), (Some("a-group"), Some("backup.json"))),
+            );
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "restore", "backup.json"])
+            .subcommand_matches("restore")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("FILE"), args.value_of("to-group")), (Some("backup.json"), None)),
+            );
+    }
+
+    #[test]
+    fn test_sync_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "sync", "variables.toml"])
+            .subcommand_matches("sync")
+            .map_or_else(
+                || panic!(),
+                |args| assert_eq!((args.value_of("MANIFEST"), args.is_present("apply")), (Some("variables.toml"), false)),
+            );
+    }
+
     #[test]
     fn test_dotenv_command() {
         app()
@@ -186,4 +666,36 @@ mod tests {
             .and_then(|args| args.value_of("GITLAB_PROJECT"))
             .map_or_else(|| panic!(), |v| assert_eq!(v, "a-project"));
     }
+
+    #[test]
+    fn test_dotenv_kubernetes_labels() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "dotenv",
+                "a-project",
+                "--format=secret",
+                "--k8s-label=team=backend",
+                "--k8s-label",
+                "tier=api",
+            ])
+            .subcommand_matches("dotenv")
+            .and_then(|args| args.values_of("k8s-label"))
+            .map_or_else(|| panic!(), |v| assert_eq!(v.collect::<Vec<&str>>(), vec!["team=backend", "tier=api"]));
+        assert!(app().get_matches_from_safe(vec!["gitlab-rescue", "dotenv", "a-project", "--k8s-label=backend"]).is_err());
+    }
+
+    #[test]
+    fn test_exec_command() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "exec", "-p", "a-project", "--", "cargo", "run", "--release"])
+            .subcommand_matches("exec")
+            .map_or_else(
+                || panic!(),
+                |args| {
+                    assert_eq!(args.value_of("GITLAB_PROJECT"), Some("a-project"));
+                    assert_eq!(args.values_of("COMMAND").unwrap().collect::<Vec<&str>>(), vec!["cargo", "run", "--release"]);
+                },
+            );
+    }
 }
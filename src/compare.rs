@@ -0,0 +1,113 @@
+#![allow(clippy::unit_arg)]
+
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::diff::diff_variables;
+use crate::dotenv::{get_list_of_variables, DotEnvCommand};
+use crate::io::IO;
+use crate::{app_info, app_success, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use urlencoding::decode;
+
+/// Arguments for `compare` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompareCommand {
+    /// Arguments for getting variables of the first project and environment
+    from: DotEnvCommand,
+    /// Arguments for getting variables of the second project and environment
+    to: DotEnvCommand,
+    /// Print values of variables
+    show_values: bool,
+}
+
+impl Performable for CompareCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            if self.from == self.to {
+                return Err(InvalidInput("Nothing to compare. Use --to-project or --to-environment options.".to_owned()));
+            }
+            app_info!("Getting variables from project {} in environment {}...", self.from.project_name(), self.from.environment());
+            let from = get_list_of_variables(&self.from)?.into_iter().map(|v| (v.key, v.value)).collect::<Vec<(String, String)>>();
+            app_info!("Getting variables from project {} in environment {}...", self.to.project_name(), self.to.environment());
+            let to = get_list_of_variables(&self.to)?.into_iter().map(|v| (v.key, v.value)).collect::<Vec<(String, String)>>();
+            let diff = diff_variables(&from, &to);
+            diff.iter().for_each(|d| println!("{}", d.describe(self.show_values)));
+            Ok(app_success!("{} difference(s) found", diff.len()))
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for CompareCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let from = DotEnvCommand::from(argm);
+        let project = argm
+            .value_of("to-project")
+            .map_or_else(|| decode(from.project_name()).unwrap_or_default(), |p| p.to_owned());
+        let environment = argm.value_of("to-environment").unwrap_or_else(|| from.environment()).to_owned();
+        CompareCommand {
+            to: from.with_target(&project, &environment),
+            from,
+            show_values: argm.is_present("show-values"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::diff::VariableDiff;
+    use crate::gitlab_api::tests::gen_variable;
+    use crate::gitlab_api::GitLabVariable;
+    use httpmock::MockServer;
+
+    fn compare_command(args: Vec<&str>) -> CompareCommand {
+        app()
+            .get_matches_from([vec!["gitlab-rescue", "compare"], args].concat())
+            .subcommand_matches("compare")
+            .map(CompareCommand::from)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_should_create_compare_cmd_from_cli_args() {
+        let cmd = compare_command(vec!["group/a-project", "-e", "staging", "--to-environment", "production"]);
+        assert_eq!((cmd.from.project_name(), cmd.from.environment()), ("group%2Fa-project", "staging"));
+        assert_eq!((cmd.to.project_name(), cmd.to.environment()), ("group%2Fa-project", "production"));
+        let cmd = compare_command(vec!["a-project", "--to-project", "b-project"]);
+        assert_eq!((cmd.to.project_name(), cmd.to.environment()), ("b-project", "All"));
+    }
+
+    #[test]
+    fn test_should_compare_two_environments() {
+        let server = MockServer::start();
+        let (shared, staging_only) = (gen_variable(None), gen_variable(None));
+        let variables = vec![
+            GitLabVariable {
+                environment_scope: "*".to_owned(),
+                ..shared.clone()
+            },
+            GitLabVariable {
+                environment_scope: "staging".to_owned(),
+                ..staging_only.clone()
+            },
+        ];
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/api/v4/projects/a-project/variables");
+            then.status(200).header("x-total", "2").json_body_obj(&variables);
+        });
+        let cmd = compare_command(vec!["a-project", "-e", "staging", "--to-environment", "production", "-u", &server.base_url()]);
+        let from = get_list_of_variables(&cmd.from)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.key, v.value))
+            .collect::<Vec<(String, String)>>();
+        let to = get_list_of_variables(&cmd.to)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.key, v.value))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(diff_variables(&from, &to), vec![VariableDiff::Removed(staging_only.key, staging_only.value)]);
+        mock.assert_hits(2);
+    }
+}
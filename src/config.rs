@@ -0,0 +1,375 @@
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::diff::project_and_file;
+use crate::repository::repository_defaults;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Shells accepted by `shell` setting of a profile, as `--shell` option
+const SHELLS: [&str; 8] = ["bash", "zsh", "fish", "powershell", "pwsh", "cmd", "nushell", "nu"];
+
+/// Configuration file of `gitlab-rescue`
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when neither `--profile` option nor `GITLAB_RESCUE_PROFILE` variable is present
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    /// Profiles by name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Named set of default values for command options
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// URL of GitLab API
+    pub url: Option<String>,
+    /// GitLab API token
+    pub token: Option<String>,
+    /// Name of the environment variable that holds the GitLab API token
+    pub token_env: Option<String>,
+    /// Path of a file that holds the GitLab API token
+    pub token_file: Option<String>,
+    /// Command that prints the GitLab API token
+    pub token_command: Option<String>,
+    /// Default project (ID or NAMESPACE/PROJECT_NAME)
+    pub project: Option<String>,
+    /// Default group (ID or path), used when the profile has no default project
+    pub group: Option<String>,
+    /// Default GitLab CI/CD environment
+    pub environment: Option<String>,
+    /// Default shell of `dotenv` command
+    pub shell: Option<String>,
+    /// URL of the selected profile, kept when defaults of the repository change `url`. Tokens of the profile are only
+    /// sent to its host (see [resolve_token](fn@crate::token::resolve_token)).
+    #[serde(skip)]
+    pub token_url: Option<String>,
+}
+
+impl Profile {
+    /// Returns the GitLab API token of the profile, taken from `token` or from the variable named by `token_env`
+    pub fn token(&self) -> Option<String> {
+        self.token.clone().or_else(|| self.token_env.as_ref().and_then(|v| env::var(v).ok()))
+    }
+
+    /// Returns a profile with the values of this profile, or the values of `other` when they are missing
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - Profile with fallback values
+    ///
+    pub fn or(self, other: Profile) -> Profile {
+        Profile {
+            url: self.url.or(other.url),
+            token: self.token.or(other.token),
+            token_env: self.token_env.or(other.token_env),
+            token_file: self.token_file.or(other.token_file),
+            token_command: self.token_command.or(other.token_command),
+            project: self.project.or(other.project),
+            group: self.group.or(other.group),
+            environment: self.environment.or(other.environment),
+            shell: self.shell.or(other.shell),
+            token_url: self.token_url.or(other.token_url),
+        }
+    }
+}
+
+impl From<&ArgMatches<'_>> for Profile {
+    /// Returns the defaults of the current repository and the profile selected by command arguments (see
+    /// [defaults](fn@defaults)), or an empty profile if they can not be read. Errors are reported before commands are
+    /// run. Commands read defaults once and pass them to the functions that complete their arguments.
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        defaults(argm).unwrap_or_default()
+    }
+}
+
+/// Returns the path of the configuration file: `GITLAB_RESCUE_CONFIG` variable if present, or
+/// `gitlab-rescue/config.toml` inside the [user's configuration folder](fn@user_config_dir)
+pub fn config_path() -> Option<PathBuf> {
+    env::var_os("GITLAB_RESCUE_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| user_config_dir().map(|d| d.join("gitlab-rescue").join("config.toml")))
+}
+
+/// Returns the user's configuration folder: `$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`
+pub fn user_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+}
+
+/// Reads a configuration file
+///
+/// # Arguments
+///
+/// * `path` - Path of the configuration file
+///
+pub fn read_config(path: &PathBuf) -> Result<Config> {
+    let content = fs::read_to_string(path).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", path.display(), e)))?;
+    toml::from_str::<Config>(&content).map_err(|e| InvalidInput(format!("File {} is not a valid configuration. Error: {}", path.display(), e)))
+}
+
+/// Returns the profile selected by `--profile` option, `GITLAB_RESCUE_PROFILE` variable or `default_profile`
+/// setting, in this order. If no profile is selected, an empty profile is returned.
+///
+/// # Arguments
+///
+/// * `argm` - Reference of [ArgMatches](ArgMatches) object
+///
+pub fn selected_profile(argm: &ArgMatches) -> Result<Profile> {
+    match (config_path(), profile_name(argm)) {
+        (Some(path), name) if path.exists() => find_profile(&read_config(&path)?, name.as_deref()),
+        (_, Some(name)) => Err(InvalidInput(format!("Profile {} not found: there is no configuration file", name))),
+        _ => Ok(Profile::default()),
+    }
+}
+
+/// Returns the name of the profile explicitly selected by `--profile` option or `GITLAB_RESCUE_PROFILE` variable
+///
+/// # Arguments
+///
+/// * `argm` - Reference of [ArgMatches](ArgMatches) object
+///
+fn profile_name(argm: &ArgMatches) -> Option<String> {
+    argm.value_of("profile").map(|p| p.to_owned()).or_else(|| env::var("GITLAB_RESCUE_PROFILE").ok())
+}
+
+/// Returns the defaults of the git repository of the current folder (see
+/// [repository_defaults](fn@crate::repository::repository_defaults)) and the selected profile, merged by
+/// [merge_defaults](fn@merge_defaults)
+///
+/// # Arguments
+///
+/// * `argm` - Reference of [ArgMatches](ArgMatches) object
+///
+pub fn defaults(argm: &ArgMatches) -> Result<Profile> {
+    let repository = match env::current_dir() {
+        Ok(dir) => repository_defaults(&dir)?,
+        _ => Profile::default(),
+    };
+    Ok(merge_defaults(repository, selected_profile(argm)?, profile_name(argm).is_some()))
+}
+
+/// Returns the defaults of the repository completed with the values of the profile, or the values of the profile
+/// completed with the defaults of the repository if the profile is explicitly selected. The URL of the profile is
+/// kept in `token_url`.
+///
+/// # Arguments
+///
+/// * `repository` - Defaults of the repository
+/// * `profile`    - Selected profile
+/// * `selected`   - Whether the profile is selected by `--profile` option or `GITLAB_RESCUE_PROFILE` variable
+///
+fn merge_defaults(repository: Profile, profile: Profile, selected: bool) -> Profile {
+    let token_url = profile.url.clone();
+    let merged = match selected {
+        true => profile.or(repository),
+        _ => repository.or(profile),
+    };
+    Profile { token_url, ..merged }
+}
+
+/// Returns the profile called `name`, or the default profile of `config` if `name` is missing
+///
+/// # Arguments
+///
+/// * `config` - Configuration
+/// * `name`   - Name of the profile
+///
+fn find_profile(config: &Config, name: Option<&str>) -> Result<Profile> {
+    match name.or(config.default_profile.as_deref()) {
+        Some(name) => config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| InvalidInput(format!("Profile {} not found in configuration file", name))),
+        None => Ok(Profile::default()),
+    }
+}
+
+/// Returns the project and group of `--project` and `--group` options, or the default project of the repository, or
+/// the default project or group of the profile if none of these options (nor `--instance`) is present. Values are not encoded.
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@defaults))
+///
+pub fn project_or_group(argm: &ArgMatches, profile: &Profile) -> (Option<String>, Option<String>) {
+    match (argm.value_of("project"), argm.value_of("group"), argm.is_present("instance")) {
+        (None, None, false) => match profile.project.clone() {
+            Some(p) => (Some(p), None),
+            None => (None, profile.group.clone()),
+        },
+        (project, group, _) => (project.map(|p| p.to_owned()), group.map(|g| g.to_owned())),
+    }
+}
+
+/// Returns the project of `GITLAB_PROJECT` argument, or the default project of the repository or the profile. The value is not encoded.
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@defaults))
+///
+pub fn project_from_args(argm: &ArgMatches, profile: &Profile) -> Option<String> {
+    argm.value_of("GITLAB_PROJECT").map(|p| p.to_owned()).or_else(|| profile.project.clone())
+}
+
+/// Checks that the defaults of the repository and the selected profile are valid, and that the arguments of
+/// `command` that have a default value (project or group) are present in arguments or in defaults
+///
+/// # Arguments
+///
+/// * `command` - Name of the subcommand
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object of the subcommand
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@defaults))
+///
+pub fn check_profile(command: &str, argm: &ArgMatches, profile: &Profile) -> Result<()> {
+    if let Some(shell) = profile.shell.as_ref().filter(|s| !SHELLS.contains(&s.as_str())) {
+        return Err(InvalidInput(format!(
+            "Default shell {} is not supported. Supported shells are: {}",
+            shell,
+            SHELLS.join(", ")
+        )));
+    }
+    let missing = match command {
+        "get" | "set" | "delete" | "import" | "copy" => project_or_group(argm, profile) == (None, None) && !argm.is_present("instance"),
+        "dotenv" | "compare" | "exec" => project_from_args(argm, profile).is_none(),
+        "diff" => project_and_file(argm, profile).0.is_none(),
+        _ => false,
+    };
+    match missing {
+        true => Err(InvalidInput(format!(
+            "A project is required by {} command. Use --project option, run it inside a GitLab repository or set a default project in a profile.",
+            command
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gen::tests::gen_alpha_char;
+
+    const CONFIG: &str = r#"
+default_profile = "work"
+
+[profiles.work]
+url = "https://gitlab.example.com"
+token_env = "WORK_GITLAB_TOKEN_FOR_TESTS"
+project = "group/app"
+environment = "staging"
+shell = "fish"
+
+[profiles.oss]
+token = "a-token"
+group = "oss-group"
+"#;
+
+    #[test]
+    fn test_read_config() {
+        let path = env::temp_dir().join(format!("config-{}.toml", gen_alpha_char(8)));
+        fs::write(&path, CONFIG).unwrap();
+        let config = read_config(&path).unwrap();
+        assert_eq!(config.default_profile, Some("work".to_owned()));
+        assert_eq!(
+            config.profiles.get("oss"),
+            Some(&Profile {
+                token: Some("a-token".to_owned()),
+                group: Some("oss-group".to_owned()),
+                ..Profile::default()
+            })
+        );
+        fs::write(&path, "[profiles.work]\nuser = \"me\"").unwrap();
+        assert!(matches!(read_config(&path), Err(InvalidInput(e)) if e.contains("is not a valid configuration")));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_find_profile() {
+        let config = toml::from_str::<Config>(CONFIG).unwrap();
+        assert_eq!(find_profile(&config, None).map(|p| p.project), Ok(Some("group/app".to_owned())));
+        assert_eq!(find_profile(&config, Some("oss")).map(|p| p.group), Ok(Some("oss-group".to_owned())));
+        assert_eq!(
+            find_profile(&config, Some("home")),
+            Err(InvalidInput("Profile home not found in configuration file".to_owned()))
+        );
+        assert_eq!(find_profile(&Config::default(), None), Ok(Profile::default()));
+    }
+
+    #[test]
+    fn test_profile_or() {
+        let config = toml::from_str::<Config>(CONFIG).unwrap();
+        let repository = Profile {
+            project: Some("group/other-app".to_owned()),
+            ..Profile::default()
+        };
+        let profile = repository.or(config.profiles["work"].clone());
+        assert_eq!((profile.project.as_deref(), profile.environment.as_deref()), (Some("group/other-app"), Some("staging")));
+    }
+
+    #[test]
+    fn test_merge_defaults() {
+        let config = toml::from_str::<Config>(CONFIG).unwrap();
+        let repository = Profile {
+            url: Some("https://gitlab.other.com".to_owned()),
+            project: Some("group/other-app".to_owned()),
+            ..Profile::default()
+        };
+        let profile = merge_defaults(repository.clone(), config.profiles["work"].clone(), false);
+        assert_eq!(
+            (profile.url.as_deref(), profile.project.as_deref(), profile.token_url.as_deref()),
+            (Some("https://gitlab.other.com"), Some("group/other-app"), Some("https://gitlab.example.com"))
+        );
+        let profile = merge_defaults(repository.clone(), config.profiles["work"].clone(), true);
+        assert_eq!(
+            (profile.url.as_deref(), profile.project.as_deref(), profile.token_url.as_deref()),
+            (Some("https://gitlab.example.com"), Some("group/app"), Some("https://gitlab.example.com"))
+        );
+        let profile = merge_defaults(repository, config.profiles["oss"].clone(), true);
+        assert_eq!(
+            (profile.url.as_deref(), profile.group.as_deref(), profile.token_url),
+            (Some("https://gitlab.other.com"), Some("oss-group"), None)
+        );
+    }
+
+    #[test]
+    fn test_profile_token() {
+        let profile = Profile {
+            token_env: Some("WORK_GITLAB_TOKEN_FOR_TESTS".to_owned()),
+            ..Profile::default()
+        };
+        env::set_var("WORK_GITLAB_TOKEN_FOR_TESTS", "env-token");
+        assert_eq!(profile.token(), Some("env-token".to_owned()));
+        let profile = Profile {
+            token: Some("a-token".to_owned()),
+            ..profile
+        };
+        assert_eq!(profile.token(), Some("a-token".to_owned()));
+    }
+
+    #[test]
+    fn test_project_or_group_from_args() {
+        let matches = app().get_matches_from(vec!["gitlab-rescue", "get", "A", "-g", "a-group"]);
+        let args = matches.subcommand_matches("get").unwrap();
+        let profile = Profile {
+            project: Some("group/app".to_owned()),
+            ..Profile::default()
+        };
+        assert_eq!(project_or_group(args, &profile), (None, Some("a-group".to_owned())));
+        assert_eq!(check_profile("get", args, &profile), Ok(()));
+        let matches = app().get_matches_from(vec!["gitlab-rescue", "get", "A"]);
+        let args = matches.subcommand_matches("get").unwrap();
+        assert_eq!(project_or_group(args, &profile), (Some("group/app".to_owned()), None));
+        assert!(check_profile("get", args, &Profile::default()).is_err());
+    }
+}
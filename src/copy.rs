@@ -0,0 +1,189 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::api_client;
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::config::Profile;
+use crate::delete_variable::source_description;
+use crate::dotenv::{all_from_api, RequestConfig};
+use crate::gitlab_api::{GitLabVariable, GitLabVariableAttributes, VariableSource};
+use crate::io::IO;
+use crate::scope::wildcard_match;
+use crate::set_variable::{source_from_args, write_variable};
+use crate::{app_info, app_success, app_warning, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use urlencoding::encode;
+
+/// Arguments for `copy` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyCommand {
+    /// Project or group to read variables from
+    from: VariableSource,
+    /// Project or group to write variables to
+    to: VariableSource,
+    /// Only copy variables defined in this environment scope
+    environment: Option<String>,
+    /// Environment scope of copied variables. By default, the original scope is kept.
+    to_environment: Option<String>,
+    /// Only copy variables whose key matches this glob pattern
+    filter: Option<String>,
+    /// Overwrite variables that already exist in destination
+    overwrite: bool,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+impl Performable for CopyCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            app_info!("Getting variables from {}...", source_description(&self.from));
+            let variables = all_from_api(RequestConfig::new(&self.url, &self.token, self.from.clone(), 1, 100), num_cpus::get())?;
+            app_info!("Getting variables from {}...", source_description(&self.to));
+            let existing = all_from_api(RequestConfig::new(&self.url, &self.token, self.to.clone(), 1, 100), num_cpus::get())?;
+            let api = api_client(&self.url, &self.token);
+            let copied = variables_to_copy(&self, &variables)?.into_iter().try_fold(0, |copied, v| {
+                match (existing.iter().any(|e| e.key == v.key && e.environment_scope == v.environment_scope), self.overwrite) {
+                    (true, false) => Ok(app_warning!("Variable {} already exists in scope {}. Skipping...", v.key, v.environment_scope)).map(|_| copied),
+                    (exists, _) => write_variable(&api, &self.to, &v, exists)
+                        .map(|_| app_info!("Variable {} copied to scope {}", v.key, v.environment_scope))
+                        .map(|_| copied + 1),
+                }
+            })?;
+            Ok(app_success!("{} variable(s) copied to {}", copied, source_description(&self.to)))
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for CopyCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        CopyCommand {
+            from: source_from_args(argm, &profile),
+            to: match argm.value_of("to-project") {
+                Some(p) => VariableSource::Project(encode(p)),
+                None => VariableSource::Group(encode(argm.value_of("to-group").unwrap())),
+            },
+            environment: argm.value_of("environment").map(|v| v.to_owned()),
+            to_environment: argm.value_of("to-environment").map(|v| v.to_owned()),
+            filter: argm.value_of("filter").map(|v| v.to_owned()),
+            overwrite: argm.is_present("overwrite"),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Returns the attributes of variables to write in destination, after filtering them by environment scope and key,
+/// and rewriting their environment scope. Fails before anything is written if several variables would be written
+/// with the same key in the same destination scope.
+///
+/// # Arguments
+///
+/// * `cmd`       - Copy command
+/// * `variables` - Variables of source project or group
+///
+fn variables_to_copy(cmd: &CopyCommand, variables: &[GitLabVariable]) -> Result<Vec<GitLabVariableAttributes>> {
+    let attributes = variables
+        .iter()
+        .filter(|v| cmd.environment.iter().all(|e| *e == v.environment_scope))
+        .filter(|v| cmd.filter.iter().all(|f| wildcard_match(f.as_bytes(), v.key.as_bytes())))
+        .map(|v| GitLabVariableAttributes {
+            environment_scope: cmd.to_environment.clone().unwrap_or_else(|| v.environment_scope.clone()),
+            ..GitLabVariableAttributes::from(v)
+        })
+        .collect::<Vec<GitLabVariableAttributes>>();
+    match attributes
+        .iter()
+        .enumerate()
+        .find(|(i, v)| attributes[..*i].iter().any(|p| p.key == v.key && p.environment_scope == v.environment_scope))
+    {
+        Some((_, v)) => Err(InvalidInput(format!(
+            "Variable {} is defined in several source scopes that would be copied to scope {}. Use --environment to copy only one of them.",
+            v.key, v.environment_scope
+        ))),
+        None => Ok(attributes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+
+    fn gen_copy_command() -> CopyCommand {
+        CopyCommand {
+            from: VariableSource::Project(GEN_PROJECT_NAME.clone()),
+            to: VariableSource::Group(GEN_GROUP_NAME.clone()),
+            environment: Some(GEN_ENVIRONMENT.clone()),
+            to_environment: Some("production".to_owned()),
+            filter: Some("DB_*".to_owned()),
+            overwrite: true,
+            url: "gitlab.com".to_owned(),
+            token: GEN_TOKEN.clone(),
+        }
+    }
+
+    #[test]
+    fn test_should_create_copy_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "copy",
+                &format!("-p={}", *GEN_PROJECT_NAME),
+                &format!("-e={}", *GEN_ENVIRONMENT),
+                &format!("--to-group={}", *GEN_GROUP_NAME),
+                "--to-environment=production",
+                "--filter=DB_*",
+                "--overwrite",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("copy")
+            .map(|a| assert_eq!(CopyCommand::from(a), gen_copy_command()))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_variables_to_copy() {
+        let gen_scoped = |key: &str, scope: &str| GitLabVariable {
+            key: key.to_owned(),
+            environment_scope: scope.to_owned(),
+            ..gen_variable(None)
+        };
+        let variables = vec![gen_scoped("DB_HOST", &GEN_ENVIRONMENT), gen_scoped("DB_USER", "B"), gen_scoped("API_KEY", &GEN_ENVIRONMENT)];
+        let copied = variables_to_copy(&gen_copy_command(), &variables).unwrap();
+        assert_eq!(
+            copied.iter().map(|v| (v.key.as_str(), v.environment_scope.as_str())).collect::<Vec<(&str, &str)>>(),
+            vec![("DB_HOST", "production")]
+        );
+        assert_eq!(copied[0].value, variables[0].value);
+        let cmd = CopyCommand {
+            environment: None,
+            to_environment: None,
+            filter: None,
+            ..gen_copy_command()
+        };
+        assert_eq!(variables_to_copy(&cmd, &variables).map(|l| l.len()), Ok(variables.len()));
+    }
+
+    #[test]
+    fn test_variables_to_copy_fails_when_scopes_collide() {
+        let gen_scoped = |key: &str, scope: &str| GitLabVariable {
+            key: key.to_owned(),
+            environment_scope: scope.to_owned(),
+            ..gen_variable(None)
+        };
+        let variables = vec![gen_scoped("DB_HOST", &GEN_ENVIRONMENT), gen_scoped("DB_HOST", "B")];
+        let cmd = CopyCommand {
+            environment: None,
+            ..gen_copy_command()
+        };
+        assert!(matches!(variables_to_copy(&cmd, &variables), Err(InvalidInput(e)) if e.contains("DB_HOST") && e.contains("production")));
+        let cmd = CopyCommand { to_environment: None, ..cmd };
+        assert_eq!(variables_to_copy(&cmd, &variables).map(|l| l.len()), Ok(2));
+    }
+}
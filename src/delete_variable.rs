@@ -0,0 +1,158 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::api_client;
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::config::Profile;
+use crate::gitlab_api::{GitLabApi, VariableSource};
+use crate::io::IO;
+use crate::set_variable::source_from_args;
+use crate::{app_info, app_success, app_warning, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use std::io::{stdin, BufRead};
+
+/// Arguments for `delete` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeleteVariableCommand {
+    /// Variable name
+    name: String,
+    /// Project or group where the variable will be deleted
+    source: VariableSource,
+    /// Environment scope of the copy of the variable to delete
+    environment: String,
+    /// Skip confirmation prompt
+    yes: bool,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+impl Performable for DeleteVariableCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            let question = format!("Delete variable {} in scope {} from {}?", self.name, self.environment, source_description(&self.source));
+            match self.yes || confirm(&question, &mut stdin().lock())? {
+                true => {
+                    app_info!("Deleting variable {} from GitLab API...", &self.name);
+                    delete_variable(&self).map(|_| app_success!("Variable {} deleted successfully from scope {}", self.name, self.environment))
+                }
+                _ => Ok(app_warning!("Variable {} was not deleted", self.name)),
+            }
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for DeleteVariableCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        DeleteVariableCommand {
+            name: argm.value_of("VARIABLE_NAME").unwrap().to_owned(),
+            source: source_from_args(argm, &profile),
+            environment: argm.value_of("environment").unwrap().to_owned(),
+            yes: argm.is_present("yes"),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Returns a human readable description of a [VariableSource](enum@VariableSource)
+pub(crate) fn source_description(source: &VariableSource) -> String {
+    match source {
+        VariableSource::Project(p) => format!("project {}", p),
+        VariableSource::Group(g) => format!("group {}", g),
+        VariableSource::Instance => "instance".to_owned(),
+    }
+}
+
+/// Prints `question` in STDERR and returns `true` if the answer read from `input` is affirmative
+///
+/// # Arguments
+///
+/// * `question` - Question to print
+/// * `input`    - Source of the answer (usually STDIN)
+///
+pub(crate) fn confirm(question: &str, input: &mut impl BufRead) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Deletes the copy of the variable defined in the specified environment scope
+fn delete_variable(cmd: &DeleteVariableCommand) -> Result<()> {
+    let api = api_client(&cmd.url, &cmd.token);
+    match &cmd.source {
+        VariableSource::Project(p) => api.delete_from_project(p, &cmd.name, &cmd.environment),
+        VariableSource::Group(g) => api.delete_from_group(g, &cmd.name, &cmd.environment),
+        VariableSource::Instance => Err(InvalidInput("Instance variables can not be deleted with this command".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+    use httpmock::MockServer;
+
+    fn gen_delete_command(url: &str, source: VariableSource) -> DeleteVariableCommand {
+        DeleteVariableCommand {
+            name: GEN_NAME.clone(),
+            source,
+            environment: GEN_ENVIRONMENT.clone(),
+            yes: true,
+            url: url.to_owned(),
+            token: GEN_TOKEN.clone(),
+        }
+    }
+
+    #[test]
+    fn test_should_create_delete_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "delete",
+                &GEN_NAME,
+                &format!("-e={}", *GEN_ENVIRONMENT),
+                &format!("-g={}", *GEN_GROUP_NAME),
+                "--yes",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("delete")
+            .map(|a| {
+                assert_eq!(
+                    DeleteVariableCommand::from(a),
+                    gen_delete_command("gitlab.com", VariableSource::Group(GEN_GROUP_NAME.clone()))
+                )
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_confirm() {
+        assert_eq!(confirm("Delete?", &mut "y\n".as_bytes()), Ok(true));
+        assert_eq!(confirm("Delete?", &mut "YES\n".as_bytes()), Ok(true));
+        assert_eq!(confirm("Delete?", &mut "\n".as_bytes()), Ok(false));
+        assert_eq!(confirm("Delete?", &mut "nope\n".as_bytes()), Ok(false));
+    }
+
+    #[test]
+    fn test_should_delete_only_scoped_copy_from_project() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("DELETE")
+                .path(format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, *GEN_NAME))
+                .query_param("filter[environment_scope]", &*GEN_ENVIRONMENT);
+            then.status(204);
+        });
+        assert_eq!(
+            delete_variable(&gen_delete_command(&server.base_url(), VariableSource::Project(GEN_PROJECT_NAME.clone()))),
+            Ok(())
+        );
+        mock.assert();
+    }
+}
@@ -0,0 +1,194 @@
+#![allow(clippy::unit_arg)]
+
+use crate::app_error::Result;
+use crate::config::{project_from_args, Profile};
+use crate::dotenv::{get_expanded_list_of_variables, DotEnvCommand};
+use crate::import::read_dotenv_file;
+use crate::io::IO;
+use crate::{app_info, app_success, Performable};
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::convert::From;
+
+/// Arguments for `diff` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffCommand {
+    /// Arguments for getting project variables the same way `dotenv` command does
+    dotenv: DotEnvCommand,
+    /// Local dotenv file to compare with
+    file: String,
+    /// Print values of variables
+    show_values: bool,
+}
+
+/// Difference of a variable between two sets of variables
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariableDiff {
+    /// Variable only defined in the second set (key and value)
+    Added(String, String),
+    /// Variable only defined in the first set (key and value)
+    Removed(String, String),
+    /// Variable defined in both sets with different values (key, first value and second value)
+    Changed(String, String, String),
+}
+
+impl VariableDiff {
+    /// Returns a line describing the difference. Values are masked unless `show_values` is `true`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use gitlab_rescue::diff::VariableDiff;
+    ///
+    /// let diff = VariableDiff::Changed("HOME".to_owned(), "/home/a".to_owned(), "/home/b".to_owned());
+    /// assert_eq!(diff.describe(false), "~ HOME");
+    /// assert_eq!(diff.describe(true), "~ HOME: \"/home/a\" -> \"/home/b\"");
+    /// ```
+    ///
+    pub fn describe(&self, show_values: bool) -> String {
+        match (self, show_values) {
+            (VariableDiff::Added(k, v), true) => format!("+ {}={:?}", k, v),
+            (VariableDiff::Added(k, _), false) => format!("+ {}", k),
+            (VariableDiff::Removed(k, v), true) => format!("- {}={:?}", k, v),
+            (VariableDiff::Removed(k, _), false) => format!("- {}", k),
+            (VariableDiff::Changed(k, a, b), true) => format!("~ {}: {:?} -> {:?}", k, a, b),
+            (VariableDiff::Changed(k, _, _), false) => format!("~ {}", k),
+        }
+    }
+}
+
+impl Performable for DiffCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            app_info!("Getting variables from project {}...", self.dotenv.project_name());
+            let remote = get_expanded_list_of_variables(&self.dotenv)?
+                .into_iter()
+                .map(|v| (v.key, v.value))
+                .collect::<Vec<(String, String)>>();
+            app_info!("Reading variables from {}...", self.file);
+            let local = read_dotenv_file(&self.file)?.into_iter().map(|(k, v, _)| (k, v)).collect::<Vec<(String, String)>>();
+            let diff = diff_variables(&remote, &local);
+            diff.iter().for_each(|d| println!("{}", d.describe(self.show_values)));
+            Ok(app_success!("{} difference(s) found", diff.len()))
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for DiffCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let dotenv = DotEnvCommand::from(argm);
+        let (project, file) = project_and_file(argm, &Profile::from(argm));
+        DiffCommand {
+            dotenv: dotenv.with_target(&project.unwrap_or_default(), dotenv.environment()),
+            file,
+            show_values: argm.is_present("show-values"),
+        }
+    }
+}
+
+/// Returns the project (not encoded) and the dotenv file of `diff` command. When only one positional argument is
+/// present, it is the dotenv file and the project is the default project of the repository or the profile.
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object of `diff` command
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@crate::config::defaults))
+///
+pub(crate) fn project_and_file(argm: &ArgMatches, profile: &Profile) -> (Option<String>, String) {
+    match argm.value_of("FILE") {
+        Some(file) => (project_from_args(argm, profile), file.to_owned()),
+        None => (profile.project.clone(), argm.value_of("GITLAB_PROJECT").unwrap_or_default().to_owned()),
+    }
+}
+
+/// Returns the differences between two sets of variables, sorted by key
+///
+/// # Arguments
+///
+/// * `from` - First set of variables (key and value)
+/// * `to`   - Second set of variables (key and value)
+///
+pub(crate) fn diff_variables(from: &[(String, String)], to: &[(String, String)]) -> Vec<VariableDiff> {
+    let (from, to) = (
+        from.iter().cloned().collect::<BTreeMap<String, String>>(),
+        to.iter().cloned().collect::<BTreeMap<String, String>>(),
+    );
+    let mut keys = from.keys().chain(to.keys()).cloned().collect::<Vec<String>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|k| match (from.get(&k), to.get(&k)) {
+            (None, Some(v)) => Some(VariableDiff::Added(k, v.clone())),
+            (Some(v), None) => Some(VariableDiff::Removed(k, v.clone())),
+            (Some(a), Some(b)) if a != b => Some(VariableDiff::Changed(k, a.clone(), b.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_should_create_diff_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "diff", "a-project", ".env.production", "-e", "production"])
+            .subcommand_matches("diff")
+            .map(|a| {
+                let cmd = DiffCommand::from(a);
+                assert_eq!((cmd.file.as_str(), cmd.show_values), (".env.production", false));
+                assert_eq!(cmd.dotenv.project_name(), "a-project");
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_should_take_default_project_if_only_the_file_is_present() {
+        let profile = Profile {
+            project: Some("group/app".to_owned()),
+            ..Profile::default()
+        };
+        let args = app().get_matches_from(vec!["gitlab-rescue", "diff", ".env.production"]);
+        assert_eq!(
+            project_and_file(args.subcommand_matches("diff").unwrap(), &profile),
+            (Some("group/app".to_owned()), ".env.production".to_owned())
+        );
+        let args = app().get_matches_from(vec!["gitlab-rescue", "diff", "a-project", ".env.production"]);
+        assert_eq!(
+            project_and_file(args.subcommand_matches("diff").unwrap(), &profile),
+            (Some("a-project".to_owned()), ".env.production".to_owned())
+        );
+        assert!(app().get_matches_from_safe(vec!["gitlab-rescue", "diff"]).is_err());
+    }
+
+    #[test]
+    fn test_diff_variables() {
+        let remote = pairs(&[("C", "3"), ("A", "1"), ("B", "2")]);
+        let local = pairs(&[("A", "1"), ("B", "two"), ("D", "4")]);
+        assert_eq!(
+            diff_variables(&remote, &local),
+            vec![
+                VariableDiff::Changed("B".to_owned(), "2".to_owned(), "two".to_owned()),
+                VariableDiff::Removed("C".to_owned(), "3".to_owned()),
+                VariableDiff::Added("D".to_owned(), "4".to_owned()),
+            ]
+        );
+        assert!(diff_variables(&remote, &remote).is_empty());
+    }
+
+    #[test]
+    fn test_describe_masks_values() {
+        let (added, removed) = (
+            VariableDiff::Added("A".to_owned(), "secret".to_owned()),
+            VariableDiff::Removed("B".to_owned(), "secret".to_owned()),
+        );
+        assert_eq!((added.describe(false), removed.describe(false)), ("+ A".to_owned(), "- B".to_owned()));
+        assert_eq!((added.describe(true), removed.describe(true)), ("+ A=\"secret\"".to_owned(), "- B=\"secret\"".to_owned()));
+    }
+}
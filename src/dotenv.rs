@@ -1,12 +1,19 @@
 #![allow(clippy::unit_arg)]
 
-use crate::api_client::{api_client, DEFAULT_ENVIRONMENT};
+use crate::api_client::api_client;
 use crate::app_error::{AppError, Result};
+use crate::config::{project_from_args, Profile};
 use crate::dotenv::AppError::{Cli, InvalidInput};
-use crate::gitlab_api::{GitLabApi, GitLabProject, GitLabVariable, GitLabVariableType};
-use crate::shell_types::ShellType;
+use crate::expand::expand_variables;
+use crate::gitlab_api::{GitLabApi, GitLabProject, GitLabVariable, GitLabVariableType, VariableSource};
+use crate::output_formats::{
+    docker_env_line, dotenv_line, is_compose_literal, kubernetes_name, systemd_env_line, ExportedVariable, KubernetesManifest, KubernetesMetadata, OutputFormat,
+};
+use crate::repository::REPOSITORY_CONFIG;
+use crate::scope::{merge_variables, resolve_variables};
+use crate::shell_types::{posix_quote, ShellType};
 use crate::IO;
-use crate::{app_info, app_warning, extract_token, extract_url, Performable};
+use crate::{app_info, app_warning, extract_environment, extract_token, extract_url, Performable};
 use clap::ArgMatches;
 use std::convert::From;
 use std::env;
@@ -14,7 +21,7 @@ use std::io::Write;
 use std::sync::mpsc::channel;
 use std::{fs, fs::File};
 use threadpool::ThreadPool;
-use urlencoding::encode;
+use urlencoding::{decode, encode};
 
 /// Arguments for `dotenv` command
 #[derive(Clone, Debug, PartialEq)]
@@ -27,14 +34,27 @@ pub struct DotEnvCommand {
     output_file: Option<String>,
     /// Generate dotenv for this shell type.
     shell: ShellType,
+    /// Output format
+    format: OutputFormat,
     /// Path where variables with type "File" will be stored.
     folder: String,
+    /// Path where `folder` is mounted inside a container. Paths of variables with type "File" point to it in
+    /// non-shell formats.
+    container_folder: Option<String>,
+    /// Name, namespace and labels of Kubernetes manifests
+    kubernetes: KubernetesMetadata,
     /// Number of items to list per page
     per_page: usize,
     /// Export group variables if project belongs to a group
     with_group_vars: bool,
+    /// Export instance variables (self-managed GitLab only)
+    with_instance_vars: bool,
+    /// Filter applied to variables by their flags
+    filter: VariableFilter,
     /// Parallelism
     parallel: usize,
+    /// Profile selected with `--profile` option
+    profile: Option<String>,
     /// GitLab instance URL
     url: String,
     /// Token to connect to GitLab instance API
@@ -44,26 +64,36 @@ pub struct DotEnvCommand {
 impl Performable for DotEnvCommand {
     fn get_action(self) -> IO<Result<()>> {
         IO::unit(move || {
+            if self.format == OutputFormat::Direnv {
+                return Ok((self.clone(), vec![]));
+            }
             app_info!("Getting variables from project {}...", self.gitlab_project.name);
-            Ok((self.clone(), get_list_of_variables(&self)?))
+            let variables = get_expanded_list_of_variables(&self)?;
+            variables
+                .iter()
+                .for_each(|v| app_info!("Variable {} taken from scope {}{}", v.key, v.environment_scope, v.details()));
+            Ok((self.clone(), variables))
         })
         .map(|res: Result<(DotEnvCommand, Vec<GitLabVariable>)>| {
             res.and_then(|(cmd, variables)| {
+                if !writes_files(&cmd) {
+                    return Ok((cmd, variables));
+                }
                 app_info!("Creating files for variables of type File...");
                 fs::create_dir_all(cmd.folder.clone()).map_err(|e| InvalidInput(format!("Folder {} could not be created. Error: {}", &cmd.folder, e)))?;
                 get_files_to_create(&cmd.folder, &variables)
                     .into_iter()
-                    .fold(Ok(()), |acc: Result<()>, (file, content)| acc.and(Ok(File::create(file)?.write_all(&content)?)))
+                    .try_fold((), |_, (file, content)| -> Result<()> { Ok(File::create(file)?.write_all(&content)?) })
                     .map_err(|e| Cli(format!("Some files could not be created. Error: {}", e)))
                     .map(|_| (cmd, variables))
             })
         })
         .map(|res: Result<(DotEnvCommand, Vec<GitLabVariable>)>| {
             res.and_then(|(cmd, variables)| {
-                app_info!("Creating dotenv command list...");
-                match (generate_commands(cmd.shell, &variables, &cmd.folder), &cmd.output_file) {
-                    (list, Some(f)) => File::create(&f)
-                        .and_then(|mut f| f.write_all(&format!("{}{}", &list.join("\n"), "\n").as_bytes().to_vec()))
+                app_info!("Creating dotenv in {} format...", cmd.format);
+                match (generate_output(&cmd, &variables)?, &cmd.output_file) {
+                    (list, Some(f)) => File::create(f)
+                        .and_then(|mut f| f.write_all(format!("{}{}", &list.join("\n"), "\n").as_bytes()))
                         .or_else(|e| {
                             app_warning!("Output file could not be created. Error: {}. Printing dotenv in STDOUT...", e);
                             Ok(list.into_iter().for_each(|c| println!("{}", c)))
@@ -75,64 +105,232 @@ impl Performable for DotEnvCommand {
     }
 }
 
+impl DotEnvCommand {
+    /// Returns the project ID or URL-encoded NAMESPACE/PROJECT_NAME
+    pub fn project_name(&self) -> &str {
+        &self.gitlab_project.name
+    }
+
+    /// Returns the name of GitLab CI/CD environment
+    pub fn environment(&self) -> &str {
+        &self.environment
+    }
+
+    /// Returns a copy of this command targeting another project and environment
+    ///
+    /// # Arguments
+    ///
+    /// * `project`     - Project ID or NAMESPACE/PROJECT_NAME (not encoded)
+    /// * `environment` - Name of GitLab CI/CD environment
+    ///
+    pub fn with_target(&self, project: &str, environment: &str) -> DotEnvCommand {
+        DotEnvCommand {
+            gitlab_project: GitLabProject {
+                name: encode(project),
+                variables: vec![],
+            },
+            environment: environment.to_owned(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this command storing variables with type "File" in another folder
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - Folder where variables with type "File" are stored
+    ///
+    pub fn with_folder(&self, folder: &str) -> DotEnvCommand {
+        DotEnvCommand {
+            folder: folder.to_owned(),
+            ..self.clone()
+        }
+    }
+}
+
 impl From<&ArgMatches<'_>> for DotEnvCommand {
     fn from(argm: &ArgMatches<'_>) -> Self {
-        assert!(argm.is_present("GITLAB_PROJECT"));
+        let profile = Profile::from(argm);
+        let project = project_from_args(argm, &profile).unwrap_or_default();
         DotEnvCommand {
             gitlab_project: GitLabProject {
-                name: encode(argm.value_of("GITLAB_PROJECT").unwrap()),
+                name: encode(&project),
                 variables: vec![],
             },
-            environment: get_env_from_args(argm),
+            environment: extract_environment!(argm, profile),
             output_file: argm.value_of("output").map(|v| v.to_owned()),
-            shell: if let Some("fish") = argm.value_of("shell") { ShellType::Fish } else { ShellType::Posix },
-            folder: argm.value_of("folder").map_or_else(|| format!(".env.{}", get_env_from_args(argm)), |v| v.to_owned()),
+            shell: argm.value_of("shell").or(profile.shell.as_deref()).map_or(ShellType::Posix, ShellType::from),
+            format: argm.value_of("format").map_or(OutputFormat::Shell, OutputFormat::from),
+            folder: argm
+                .value_of("folder")
+                .map_or_else(|| format!(".env.{}", extract_environment!(argm, profile)), |v| v.to_owned()),
+            container_folder: argm.value_of("container-folder").map(|v| v.trim_end_matches('/').to_owned()),
+            kubernetes: KubernetesMetadata {
+                name: argm.value_of("k8s-name").map_or_else(
+                    || {
+                        let name = project.rsplit('/').next().unwrap();
+                        kubernetes_name(&format!("{}-{}", name, extract_environment!(argm, profile)))
+                    },
+                    |v| v.to_owned(),
+                ),
+                namespace: argm.value_of("k8s-namespace").map(|v| v.to_owned()),
+                labels: argm
+                    .values_of("k8s-label")
+                    .map_or_else(Vec::new, |l| l.collect())
+                    .into_iter()
+                    .filter_map(|l| l.split_once('='))
+                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .collect(),
+            },
             per_page: numeric_param_from_args(argm, "per-page", 50),
             with_group_vars: argm.is_present("with-group-vars"),
+            with_instance_vars: argm.is_present("with-instance-vars"),
+            filter: VariableFilter {
+                protected: match (argm.is_present("only-protected"), argm.is_present("exclude-protected")) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                exclude_masked: argm.is_present("exclude-masked"),
+            },
             parallel: numeric_param_from_args(argm, "parallel", num_cpus::get()),
-            url: extract_url!(argm),
-            token: extract_token!(argm),
+            profile: argm.value_of("profile").map(|v| v.to_owned()),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
         }
     }
 }
 
+/// Filter applied to variables by their flags, before their scope is resolved. This way, a key whose most specific
+/// copy is filtered out falls back to a less specific copy, as it does in GitLab jobs that can not see that copy.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VariableFilter {
+    /// Keep only protected variables (`Some(true)`) or only unprotected variables (`Some(false)`)
+    protected: Option<bool>,
+    /// Exclude masked variables
+    exclude_masked: bool,
+}
+
+impl VariableFilter {
+    /// Returns variables that pass the filter
+    ///
+    /// # Arguments
+    ///
+    /// * `variables` - List of GitLab variables
+    ///
+    fn apply(&self, variables: Vec<GitLabVariable>) -> Vec<GitLabVariable> {
+        variables
+            .into_iter()
+            .filter(|v| self.protected.unwrap_or(v.protected) == v.protected && !(self.exclude_masked && v.masked))
+            .collect()
+    }
+}
+
 /// GitLab requests configuration
 #[derive(Clone, Debug, PartialEq)]
 pub struct RequestConfig {
     /// GitLab instance URL
-    url: String,
-    /// GitLab instance URL
-    token: String,
-    /// GitLab instance URL
-    gitlab_project: String,
+    pub(crate) url: String,
+    /// Token to connect to GitLab instance API
+    pub(crate) token: String,
+    /// Project or group to request variables from
+    pub(crate) source: VariableSource,
     /// Page to request
-    page: usize,
+    pub(crate) page: usize,
     /// Number of items to list per page
-    per_page: usize,
+    pub(crate) per_page: usize,
 }
 
 impl RequestConfig {
-    fn from(cmd: &DotEnvCommand, page: usize) -> Self {
+    /// Returns a new [RequestConfig](struct@RequestConfig) object
+    pub(crate) fn new(url: &str, token: &str, source: VariableSource, page: usize, per_page: usize) -> Self {
         RequestConfig {
-            url: cmd.url.clone(),
-            token: cmd.token.clone(),
-            gitlab_project: cmd.gitlab_project.name.clone(),
+            url: url.to_owned(),
+            token: token.to_owned(),
+            source,
             page,
-            per_page: cmd.per_page,
+            per_page,
         }
     }
+
+    fn from(cmd: &DotEnvCommand, page: usize) -> Self {
+        RequestConfig::new(&cmd.url, &cmd.token, VariableSource::Project(cmd.gitlab_project.name.clone()), page, cmd.per_page)
+    }
+}
+
+/// Get list of variables to export in dotenv commands, one per key from its most specific scope. If
+/// `with_group_vars` is set, variables inherited from the project's group hierarchy are included, with
+/// project variables taking precedence over group variables and child groups over parent groups. If
+/// `with_instance_vars` is set, instance variables are included with the lowest precedence. Values are returned as
+/// they are defined in GitLab.
+pub(crate) fn get_list_of_variables(cmd: &DotEnvCommand) -> Result<Vec<GitLabVariable>> {
+    let project_variables = resolve_variables(cmd.filter.apply(all_from_api(RequestConfig::from(cmd, 1), cmd.parallel)?), &cmd.environment);
+    let group_variables = if cmd.with_group_vars { get_group_variables(cmd)? } else { vec![] };
+    let instance_variables = if cmd.with_instance_vars { vec![get_instance_variables(cmd)?] } else { vec![] };
+    Ok(merge_variables([vec![project_variables], group_variables, instance_variables].concat()))
 }
 
-/// Get list of variables to export in dotenv commands
-fn get_list_of_variables(cmd: &DotEnvCommand) -> Result<Vec<GitLabVariable>> {
-    Ok(list_from_api(RequestConfig::from(&cmd, 1))
-        .and_then(|(list, total)| match list.len() == total {
-            true => Ok(list),
-            _ => Ok([list, remaining_from_api(RequestConfig::from(&cmd, 2), num_requests(total, cmd.per_page), cmd.parallel)?].concat()),
-        })?
+/// Get list of variables to export, as [get_list_of_variables](fn@get_list_of_variables) does, with references to
+/// other variables inside values expanded
+pub(crate) fn get_expanded_list_of_variables(cmd: &DotEnvCommand) -> Result<Vec<GitLabVariable>> {
+    let (variables, warnings) = expand_variables(get_list_of_variables(cmd)?, Some(files_folder(cmd)));
+    warnings.iter().for_each(|w| app_warning!("{}", w));
+    Ok(variables)
+}
+
+/// Returns variables of GitLab instance
+fn get_instance_variables(cmd: &DotEnvCommand) -> Result<Vec<GitLabVariable>> {
+    app_info!("Getting variables from instance...");
+    let request = RequestConfig {
+        source: VariableSource::Instance,
+        ..RequestConfig::from(cmd, 1)
+    };
+    Ok(resolve_variables(cmd.filter.apply(all_from_api(request, cmd.parallel)?), &cmd.environment))
+}
+
+/// Returns variables of the project's group and all its ancestors, from the closest group to the root group
+fn get_group_variables(cmd: &DotEnvCommand) -> Result<Vec<Vec<GitLabVariable>>> {
+    api_client(&cmd.url, &cmd.token)
+        .get_project_namespace(&cmd.gitlab_project.name)?
+        .group_hierarchy()
         .into_iter()
-        .filter(|v| v.environment_scope == DEFAULT_ENVIRONMENT || v.environment_scope == cmd.environment)
-        .collect())
+        .map(|group| {
+            app_info!("Getting variables from group {}...", group);
+            let request = RequestConfig {
+                source: VariableSource::Group(encode(&group)),
+                ..RequestConfig::from(cmd, 1)
+            };
+            Ok(resolve_variables(cmd.filter.apply(all_from_api(request, cmd.parallel)?), &cmd.environment))
+        })
+        .collect()
+}
+
+/// Returns all variables of a project, group or instance, requesting remaining pages in parallel when the first page
+/// does not contain all of them
+///
+/// # Arguments
+///
+/// * `request`  - Request parameters for the first page
+/// * `parallel` - Number of threads for remaining requests
+///
+pub(crate) fn all_from_api(request: RequestConfig, parallel: usize) -> Result<Vec<GitLabVariable>> {
+    all_pages_from_api(request, parallel, list_from_api)
+}
+
+/// Returns all items of a paginated GitLab API resource, requesting remaining pages in parallel when the first page
+/// does not contain all of them
+///
+/// # Arguments
+///
+/// * `request`  - Request parameters for the first page
+/// * `parallel` - Number of threads for remaining requests
+/// * `list`     - Function that requests a single page
+///
+pub(crate) fn all_pages_from_api<T: Clone + Send + 'static>(request: RequestConfig, parallel: usize, list: fn(RequestConfig) -> Result<(Vec<T>, usize)>) -> Result<Vec<T>> {
+    list(request.clone()).and_then(|(items, total)| match items.len() >= total {
+        true => Ok(items),
+        _ => Ok([items, remaining_from_api(request.clone(), num_requests(total, request.per_page), parallel, list)?].concat()),
+    })
 }
 
 /// Returns a list of variables according to some request parameters
@@ -142,37 +340,36 @@ fn get_list_of_variables(cmd: &DotEnvCommand) -> Result<Vec<GitLabVariable>> {
 /// * `request` - Request parameters
 ///
 fn list_from_api(request: RequestConfig) -> Result<(Vec<GitLabVariable>, usize)> {
-    api_client(&request.url, &request.token).list_from_project(&request.gitlab_project, request.page, request.per_page)
+    match &request.source {
+        VariableSource::Project(p) => api_client(&request.url, &request.token).list_from_project(p, request.page, request.per_page),
+        VariableSource::Group(g) => api_client(&request.url, &request.token).list_from_group(g, request.page, request.per_page),
+        VariableSource::Instance => api_client(&request.url, &request.token).list_from_instance(request.page, request.per_page),
+    }
 }
 
-/// Returns a list with remaining variables that could not be obtained in the first request
+/// Returns a list with remaining items that could not be obtained in the first request
 ///
 /// # Arguments
 ///
-/// * `requests` - Number of requests to make to obtain the remaining variables
+/// * `requests` - Number of requests to make to obtain the remaining items
 ///
-fn remaining_from_api(request: RequestConfig, num_requests: usize, parallel: usize) -> Result<Vec<GitLabVariable>> {
+fn remaining_from_api<T: Clone + Send + 'static>(
+    request: RequestConfig,
+    num_requests: usize,
+    parallel: usize,
+    list: fn(RequestConfig) -> Result<(Vec<T>, usize)>,
+) -> Result<Vec<T>> {
     let pool = ThreadPool::new(parallel);
     let (tx, rx) = channel();
     (0..num_requests)
         .fold(rx, |acc, i| {
             let (tx, r) = (tx.clone(), RequestConfig { page: i + 2, ..request.clone() });
-            pool.execute(move || tx.send(list_from_api(r)).expect("Thread Error"));
+            pool.execute(move || tx.send(list(r)).expect("Thread Error"));
             acc
         })
         .into_iter()
         .take(num_requests)
-        .fold(Ok(vec![]), |a: Result<Vec<GitLabVariable>>, res| a.and_then(|acc| Ok([acc, res.map(|(l, _)| l)?].concat())))
-}
-
-/// Returns environment name from [ArgMatches](struct@clap::ArgMatches) object
-///
-/// # Arguments
-///
-/// * `args` - Reference of [ArgMatches](ArgMatches) object
-///
-fn get_env_from_args(args: &clap::ArgMatches) -> String {
-    args.value_of("environment").map_or_else(|| DEFAULT_ENVIRONMENT.to_owned(), |v| v.to_owned())
+        .try_fold(vec![], |acc: Vec<T>, res| Ok([acc, res.map(|(l, _)| l)?].concat()))
 }
 
 /// Returns param of type `usize` from [ArgMatches](struct@clap::ArgMatches) object
@@ -210,7 +407,7 @@ fn num_requests(total: usize, per_request: usize) -> usize {
 /// * `folder` - Folder where files will be created
 /// * `variables` - List of GitLab variables
 ///
-fn get_files_to_create(folder: &str, variables: &[GitLabVariable]) -> Vec<(String, Vec<u8>)> {
+pub(crate) fn get_files_to_create(folder: &str, variables: &[GitLabVariable]) -> Vec<(String, Vec<u8>)> {
     variables
         .iter()
         .filter(|v| matches!(v.variable_type, GitLabVariableType::File))
@@ -218,7 +415,169 @@ fn get_files_to_create(folder: &str, variables: &[GitLabVariable]) -> Vec<(Strin
         .collect()
 }
 
-/// Generates a list of commands for exporting all variables in user's shell
+/// Generates the lines of the dotenv in the output format of the command
+///
+/// # Arguments
+///
+/// * `cmd` - Dotenv command
+/// * `variables` - List of GitLab variables
+///
+fn generate_output(cmd: &DotEnvCommand, variables: &[GitLabVariable]) -> Result<Vec<String>> {
+    let file_path = |v: &GitLabVariable| format!("{}/{}.var", files_folder(cmd), v.key);
+    let exported = || {
+        variables
+            .iter()
+            .map(|v| ExportedVariable {
+                key: v.key.clone(),
+                value: v.value.clone(),
+                environment_scope: v.environment_scope.clone(),
+                variable_type: v.variable_type,
+                protected: v.protected,
+                masked: v.masked,
+                raw: v.raw,
+                description: v.description.clone(),
+                file: matches!(v.variable_type, GitLabVariableType::File).then(|| file_path(v)),
+            })
+            .collect::<Vec<ExportedVariable>>()
+    };
+    match cmd.format {
+        OutputFormat::Shell => Ok(generate_commands(cmd.shell, variables, &cmd.folder)),
+        OutputFormat::Dotenv => Ok(variables
+            .iter()
+            .filter_map(|v| {
+                let line = match v.variable_type {
+                    GitLabVariableType::File => dotenv_line(&v.key, &file_path(v)),
+                    GitLabVariableType::EnvVar => dotenv_line(&v.key, &v.value),
+                };
+                if line.is_none() {
+                    app_warning!(
+                        "Variable {} is skipped because docker-compose and python-dotenv read its value differently. Use a variable of type File instead.",
+                        v.key
+                    );
+                }
+                line
+            })
+            .collect()),
+        OutputFormat::Docker => Ok(variables
+            .iter()
+            .filter_map(|v| {
+                let value = match v.variable_type {
+                    GitLabVariableType::File => file_path(v),
+                    GitLabVariableType::EnvVar => v.value.clone(),
+                };
+                match docker_env_line(&v.key, &value) {
+                    Some(line) if !is_compose_literal(&value) => {
+                        app_warning!(
+                            "Variable {} is read with a different value by docker-compose. Use --format dotenv for docker-compose.",
+                            v.key
+                        );
+                        Some(line)
+                    }
+                    None => {
+                        app_warning!(
+                            "Variable {} is skipped because Docker env-files can not hold multi-line values. Use a variable of type File instead.",
+                            v.key
+                        );
+                        None
+                    }
+                    line => line,
+                }
+            })
+            .collect()),
+        OutputFormat::Json => serde_json::to_string_pretty(&exported()).map(|s| vec![s]).map_err(|e| Cli(format!("{}", e))),
+        OutputFormat::Yaml => serde_yaml::to_string(&exported()).map(|s| vec![s.trim_end().to_owned()]).map_err(|e| Cli(format!("{}", e))),
+        OutputFormat::Systemd => Ok(variables
+            .iter()
+            .map(|v| match v.variable_type {
+                GitLabVariableType::File => systemd_env_line(&v.key, &file_path(v)),
+                GitLabVariableType::EnvVar => systemd_env_line(&v.key, &v.value),
+            })
+            .collect()),
+        OutputFormat::Direnv => Ok(generate_envrc(cmd)),
+        OutputFormat::Secret | OutputFormat::ConfigMap => {
+            let entries = variables
+                .iter()
+                .map(|v| match v.variable_type {
+                    GitLabVariableType::File => (format!("{}.var", v.key), v.value.clone()),
+                    GitLabVariableType::EnvVar => (v.key.clone(), v.value.clone()),
+                })
+                .collect();
+            let manifest = match cmd.format {
+                OutputFormat::Secret => KubernetesManifest::secret(cmd.kubernetes.clone(), entries),
+                _ => KubernetesManifest::config_map(cmd.kubernetes.clone(), entries),
+            };
+            serde_yaml::to_string(&manifest).map(|s| vec![s.trim_end().to_owned()]).map_err(|e| Cli(format!("{}", e)))
+        }
+    }
+}
+
+/// Returns `true` if files of variables with type "File" are created in the folder of the command. Only shell
+/// commands and dotenv lines read them from the host: other formats embed values, point to a folder inside a
+/// container or, for direnv, load variables later.
+///
+/// # Arguments
+///
+/// * `cmd` - Dotenv command
+///
+fn writes_files(cmd: &DotEnvCommand) -> bool {
+    match cmd.format {
+        OutputFormat::Shell => true,
+        OutputFormat::Dotenv => cmd.container_folder.is_none(),
+        _ => false,
+    }
+}
+
+/// Returns the folder that paths of variables with type "File" point to: the folder inside the container for
+/// non-shell formats if `--container-folder` is present, or the folder where files are created
+fn files_folder(cmd: &DotEnvCommand) -> &str {
+    match (cmd.format, &cmd.container_folder) {
+        (OutputFormat::Shell, _) | (OutputFormat::Direnv, _) | (_, None) => &cmd.folder,
+        (_, Some(f)) => f,
+    }
+}
+
+/// Generates an `.envrc` file for direnv that runs this command each time direnv loads it, so values are always
+/// taken from GitLab. The output of the command is pinned to bash commands, which is what direnv evaluates. The
+/// repository configuration is watched, so direnv loads variables again when it changes. The profile is forwarded if
+/// `--profile` option was used. The token is not written: the generated command resolves it again (see
+/// [resolve_token](fn@crate::token::resolve_token)).
+///
+/// # Arguments
+///
+/// * `cmd` - Dotenv command
+///
+fn generate_envrc(cmd: &DotEnvCommand) -> Vec<String> {
+    let project = decode(&cmd.gitlab_project.name).unwrap_or_else(|_| cmd.gitlab_project.name.clone());
+    let mut args = vec![
+        "dotenv".to_owned(),
+        posix_quote(&project),
+        format!("--environment={}", posix_quote(&cmd.environment)),
+        format!("--folder={}", posix_quote(&cmd.folder)),
+        format!("--url={}", posix_quote(&cmd.url)),
+        "--format=shell".to_owned(),
+        "--shell=bash".to_owned(),
+    ];
+    args.extend(cmd.profile.iter().map(|p| format!("--profile={}", posix_quote(p))));
+    let flags = [
+        (cmd.with_group_vars, "--with-group-vars"),
+        (cmd.with_instance_vars, "--with-instance-vars"),
+        (cmd.filter.protected == Some(true), "--only-protected"),
+        (cmd.filter.protected == Some(false), "--exclude-protected"),
+        (cmd.filter.exclude_masked, "--exclude-masked"),
+    ];
+    args.extend(flags.iter().filter(|(present, _)| *present).map(|(_, flag)| flag.to_string()));
+    vec![
+        format!(
+            "# Generated by gitlab-rescue. Variables of project {} are loaded from GitLab each time direnv loads this file.",
+            project
+        ),
+        format!("watch_file {}", posix_quote(REPOSITORY_CONFIG)),
+        format!("eval \"$(gitlab-rescue {})\"", args.join(" ")),
+    ]
+}
+
+/// Generates a list of commands for exporting all variables in user's shell. Variables whose value can not be
+/// exported by the shell are skipped with a warning.
 ///
 /// # Arguments
 ///
@@ -228,23 +587,32 @@ fn get_files_to_create(folder: &str, variables: &[GitLabVariable]) -> Vec<(Strin
 ///
 fn generate_commands(shell: ShellType, variables: &[GitLabVariable], folder: &str) -> Vec<String> {
     variables.iter().fold(vec![], |mut acc, v| {
-        acc.push(shell.export_command(
-            v.key.clone(),
-            match v.variable_type {
-                GitLabVariableType::File => format!("{}/{}.var", folder, v.key),
-                GitLabVariableType::EnvVar => v.value.clone(),
-            },
-        ));
+        let value = match v.variable_type {
+            GitLabVariableType::File => shell.file_path(folder, &v.key),
+            GitLabVariableType::EnvVar => v.value.clone(),
+        };
+        match shell.can_export(&value) {
+            true => acc.push(shell.export_command(v.key.clone(), value)),
+            _ => app_warning!(
+                "Variable {} is skipped because {} can not export values with new lines or double quotes. Use a variable of type File instead.",
+                v.key,
+                shell
+            ),
+        }
         acc
     })
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_option_map_or_else)]
 mod tests {
     use super::*;
+    use crate::api_client::DEFAULT_ENVIRONMENT;
     use crate::clap_app::app;
     use crate::gen::tests::*;
-    use crate::gitlab_api::tests::{gen_variable, httpmock_list_variables, GEN_GITLAB_PROJECT};
+    use crate::gitlab_api::tests::{
+        gen_variable, httpmock_list_group_variables, httpmock_list_instance_variables, httpmock_list_variables, httpmock_project_namespace, GEN_GITLAB_PROJECT,
+    };
     use crate::shell_types::tests::GEN_SHELL_TYPE;
     use httpmock::MockServer;
     use lazy_static::lazy_static;
@@ -267,10 +635,19 @@ mod tests {
             environment: GEN_ENVIRONMENT.clone(),
             output_file: Some(GEN_OUTPUT_FILE.clone()),
             shell: *GEN_SHELL_TYPE,
+            format: OutputFormat::Shell,
             folder: GEN_FOLDER.clone(),
+            container_folder: None,
+            kubernetes: KubernetesMetadata {
+                name: kubernetes_name(&format!("{}-{}", GEN_GITLAB_PROJECT.name, *GEN_ENVIRONMENT)),
+                ..KubernetesMetadata::default()
+            },
             per_page: *GEN_PER_PAGE,
             with_group_vars: *GEN_GROUP_VARS,
+            with_instance_vars: false,
+            filter: VariableFilter::default(),
             parallel: num_cpus::get(),
+            profile: None,
             url: url.map_or_else(|| GEN_URL.clone(), |u| u),
             token: GEN_TOKEN.clone(),
         }
@@ -280,7 +657,7 @@ mod tests {
         RequestConfig {
             url: url.map_or_else(|| GEN_URL.clone(), |u| u),
             token: GEN_TOKEN.clone(),
-            gitlab_project: GEN_GITLAB_PROJECT.name.clone(),
+            source: VariableSource::Project(GEN_GITLAB_PROJECT.name.clone()),
             page: *GEN_PAGE,
             per_page: *GEN_PER_PAGE,
         }
@@ -293,20 +670,25 @@ mod tests {
 
     #[test]
     fn get_dotenv_from_cli_args() {
+        let mut args = vec![
+            "gitlab-rescue".to_owned(),
+            "dotenv".to_owned(),
+            GEN_GITLAB_PROJECT.name.clone(),
+            format!("-e={}", *GEN_ENVIRONMENT),
+            format!("-o={}", *GEN_OUTPUT_FILE),
+            format!("--folder={}", *GEN_FOLDER),
+            format!("--shell={}", *GEN_SHELL_TYPE),
+            "--format=shell".to_owned(),
+            format!("--per-page={}", *GEN_PER_PAGE),
+            format!("--parallel={}", num_cpus::get()),
+            format!("-u={}", *GEN_URL),
+            format!("-t={}", *GEN_TOKEN),
+        ];
+        if *GEN_GROUP_VARS {
+            args.push("--with-group-vars".to_owned());
+        }
         app()
-            .get_matches_from(vec![
-                "gitlab-rescue",
-                "dotenv",
-                &GEN_GITLAB_PROJECT.name,
-                &format!("-e={}", *GEN_ENVIRONMENT),
-                &format!("-o={}", *GEN_OUTPUT_FILE),
-                &format!("--folder={}", *GEN_FOLDER),
-                &format!("--shell={}", *GEN_SHELL_TYPE),
-                &format!("--per-page={}", *GEN_PER_PAGE),
-                &format!("--parallel={}", num_cpus::get()),
-                &format!("-u={}", *GEN_URL),
-                &format!("-t={}", *GEN_TOKEN),
-            ])
+            .get_matches_from(args)
             .subcommand_matches("dotenv")
             .map_or_else(|| panic!(), |args| assert_eq!(DotEnvCommand::from(args), gen_dotenv_command(None)));
     }
@@ -316,17 +698,156 @@ mod tests {
         let server = MockServer::start();
         let mock = server.mock(httpmock_list_variables(*GEN_TOTAL, *GEN_PER_PAGE));
         assert!(get_list_of_variables(&gen_dotenv_command(Some(server.base_url())))
-            .map(|l| l.into_iter().all(|v| v.environment_scope == *GEN_ENVIRONMENT || v.environment_scope == DEFAULT_ENVIRONMENT))
+            .map(|l| l.iter().all(|v| v.environment_scope == *GEN_ENVIRONMENT || v.environment_scope == DEFAULT_ENVIRONMENT)
+                && l.iter().all(|v| l.iter().filter(|o| o.key == v.key).count() == 1))
             .unwrap());
         mock.assert_hits(if *GEN_PER_PAGE >= *GEN_TOTAL { 1 } else { num_requests(*GEN_TOTAL, *GEN_PER_PAGE) + 1 });
     }
 
+    #[test]
+    fn test_only_expanded_list_of_variables_expands_references() {
+        let reference = |key: &str, value: &str| GitLabVariable {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            environment_scope: DEFAULT_ENVIRONMENT.to_owned(),
+            raw: false,
+            ..gen_variable(Some(GitLabVariableType::EnvVar))
+        };
+        let list = vec![reference("URL", "https://$HOST"), reference("HOST", "example.com")];
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}/variables", GEN_GITLAB_PROJECT.name));
+            then.status(200).header("x-total", "2").json_body_obj(&list);
+        });
+        let cmd = DotEnvCommand {
+            with_group_vars: false,
+            ..gen_dotenv_command(Some(server.base_url()))
+        };
+        let url = |variables: Result<Vec<GitLabVariable>>| variables.map(|l| l.into_iter().find(|v| v.key == "URL").unwrap().value);
+        assert_eq!(url(get_list_of_variables(&cmd)), Ok("https://$HOST".to_owned()));
+        assert_eq!(url(get_expanded_list_of_variables(&cmd)), Ok("https://example.com".to_owned()));
+        mock.assert_hits(2);
+    }
+
+    #[test]
+    fn test_get_list_of_variables_with_group_vars() {
+        let server = MockServer::start();
+        let project_mock = server.mock(httpmock_list_variables(*GEN_TOTAL, *GEN_PER_PAGE));
+        let namespace_mock = server.mock(httpmock_project_namespace("group", "a-group/a-subgroup"));
+        let group_variable = GitLabVariable {
+            key: "GROUP_VARIABLE".to_owned(),
+            environment_scope: "*".to_owned(),
+            ..gen_variable(None)
+        };
+        let subgroup_variable = GitLabVariable {
+            value: gen_alpha_char(10),
+            ..group_variable.clone()
+        };
+        let group_mock = server.mock(httpmock_list_group_variables("a-group".to_owned(), vec![group_variable]));
+        let subgroup_mock = server.mock(httpmock_list_group_variables("a-group%2Fa-subgroup".to_owned(), vec![subgroup_variable.clone()]));
+        let cmd = DotEnvCommand {
+            with_group_vars: true,
+            ..gen_dotenv_command(Some(server.base_url()))
+        };
+        get_list_of_variables(&cmd)
+            .map(|l| {
+                assert_eq!(
+                    l.into_iter().filter(|v| v.key == "GROUP_VARIABLE").map(|v| v.value).collect::<Vec<String>>(),
+                    vec![subgroup_variable.value]
+                )
+            })
+            .unwrap();
+        project_mock.assert_hits(if *GEN_PER_PAGE >= *GEN_TOTAL { 1 } else { num_requests(*GEN_TOTAL, *GEN_PER_PAGE) + 1 });
+        namespace_mock.assert();
+        group_mock.assert();
+        subgroup_mock.assert();
+    }
+
+    #[test]
+    fn test_get_list_of_variables_with_instance_vars() {
+        let server = MockServer::start();
+        let project_mock = server.mock(httpmock_list_variables(*GEN_TOTAL, *GEN_PER_PAGE));
+        let instance_variable = GitLabVariable {
+            key: "INSTANCE_VARIABLE".to_owned(),
+            environment_scope: "*".to_owned(),
+            ..gen_variable(None)
+        };
+        let instance_mock = server.mock(httpmock_list_instance_variables(vec![instance_variable.clone()]));
+        let cmd = DotEnvCommand {
+            with_group_vars: false,
+            with_instance_vars: true,
+            ..gen_dotenv_command(Some(server.base_url()))
+        };
+        get_list_of_variables(&cmd)
+            .map(|l| assert_eq!(l.last().map(|v| v.value.clone()), Some(instance_variable.value)))
+            .unwrap();
+        project_mock.assert_hits(if *GEN_PER_PAGE >= *GEN_TOTAL { 1 } else { num_requests(*GEN_TOTAL, *GEN_PER_PAGE) + 1 });
+        instance_mock.assert();
+    }
+
+    #[test]
+    fn test_variable_filter_falls_back_to_less_specific_scope() {
+        let gen_flagged = |scope: &str, protected: bool, masked: bool| GitLabVariable {
+            key: "SECRET".to_owned(),
+            environment_scope: scope.to_owned(),
+            protected,
+            masked,
+            ..gen_variable(None)
+        };
+        let variables = vec![gen_flagged(DEFAULT_ENVIRONMENT, false, false), gen_flagged("production", true, true)];
+        let resolve = |filter: VariableFilter| resolve_variables(filter.apply(variables.clone()), "production");
+        assert_eq!(resolve(VariableFilter::default()), vec![variables[1].clone()]);
+        assert_eq!(
+            resolve(VariableFilter {
+                protected: Some(false),
+                exclude_masked: false
+            }),
+            vec![variables[0].clone()]
+        );
+        assert_eq!(
+            resolve(VariableFilter {
+                protected: None,
+                exclude_masked: true
+            }),
+            vec![variables[0].clone()]
+        );
+        assert_eq!(
+            resolve(VariableFilter {
+                protected: Some(true),
+                exclude_masked: false
+            }),
+            vec![variables[1].clone()]
+        );
+    }
+
+    #[test]
+    fn get_dotenv_filter_from_cli_args() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "dotenv", "a-project", "--exclude-protected", "--exclude-masked"])
+            .subcommand_matches("dotenv")
+            .map_or_else(
+                || panic!(),
+                |args| {
+                    assert_eq!(
+                        DotEnvCommand::from(args).filter,
+                        VariableFilter {
+                            protected: Some(false),
+                            exclude_masked: true
+                        }
+                    )
+                },
+            );
+        assert!(app()
+            .get_matches_from_safe(vec!["gitlab-rescue", "dotenv", "a-project", "--only-protected", "--exclude-protected"])
+            .is_err());
+    }
+
     #[test]
     fn test_remaining_from_api() {
         let server = MockServer::start();
         let mock = server.mock(httpmock_list_variables(*GEN_TOTAL, *GEN_PER_PAGE));
         let num_requests = num_requests(*GEN_TOTAL, *GEN_PER_PAGE);
-        remaining_from_api(gen_request_config(Some(server.base_url())), num_requests, num_cpus::get())
+        remaining_from_api(gen_request_config(Some(server.base_url())), num_requests, num_cpus::get(), list_from_api)
             .map_or_else(|_| panic!(), |list| assert_eq!(list.len(), num_requests * *GEN_PER_PAGE));
         mock.assert_hits(num_requests);
     }
@@ -359,4 +880,208 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_generate_commands_for_windows_shells() {
+        let file_variable = gen_variable(Some(GitLabVariableType::File));
+        let multiline = GitLabVariable {
+            value: "first\nsecond".to_owned(),
+            ..gen_variable(Some(GitLabVariableType::EnvVar))
+        };
+        let quoted = GitLabVariable {
+            value: "100% \"&calc".to_owned(),
+            ..gen_variable(Some(GitLabVariableType::EnvVar))
+        };
+        assert_eq!(
+            generate_commands(ShellType::Cmd, &[multiline.clone(), quoted, file_variable.clone()], ".env.review/x"),
+            vec![format!("set \"{}=.env.review\\x\\{}.var\"", file_variable.key, file_variable.key)]
+        );
+        assert_eq!(
+            generate_commands(ShellType::PowerShell, std::slice::from_ref(&multiline), ".env.review/x"),
+            vec![format!("$env:{} = 'first\nsecond'", multiline.key)]
+        );
+    }
+
+    #[test]
+    fn test_generate_output_in_structured_formats() {
+        let env_variable = GitLabVariable {
+            key: "GREETING".to_owned(),
+            value: "it's".to_owned(),
+            environment_scope: "*".to_owned(),
+            protected: true,
+            masked: false,
+            raw: true,
+            description: Some("Greeting of the app".to_owned()),
+            ..gen_variable(Some(GitLabVariableType::EnvVar))
+        };
+        let file_variable = GitLabVariable {
+            key: "CERT".to_owned(),
+            value: "certificate".to_owned(),
+            environment_scope: "production".to_owned(),
+            protected: false,
+            masked: true,
+            raw: false,
+            description: None,
+            ..gen_variable(Some(GitLabVariableType::File))
+        };
+        let variables = [env_variable, file_variable];
+        let output = |format| {
+            generate_output(
+                &DotEnvCommand {
+                    format,
+                    ..gen_dotenv_command(None)
+                },
+                &variables,
+            )
+        };
+        assert_eq!(
+            output(OutputFormat::Dotenv),
+            Ok(vec!["GREETING=\"it's\"".to_owned(), format!("CERT='{}/CERT.var'", *GEN_FOLDER)])
+        );
+        let json = serde_json::from_str::<serde_json::Value>(&output(OutputFormat::Json).unwrap().concat()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "key": "GREETING", "value": "it's", "environment_scope": "*", "variable_type": "env_var",
+                    "protected": true, "masked": false, "raw": true, "description": "Greeting of the app"
+                },
+                {
+                    "key": "CERT", "value": "certificate", "environment_scope": "production", "variable_type": "file",
+                    "protected": false, "masked": true, "raw": false, "file": format!("{}/CERT.var", *GEN_FOLDER)
+                }
+            ])
+        );
+        let yaml = serde_yaml::from_str::<serde_json::Value>(&output(OutputFormat::Yaml).unwrap().concat()).unwrap();
+        assert_eq!(yaml, json);
+    }
+
+    #[test]
+    fn test_generate_output_in_docker_format() {
+        let variables = [
+            GitLabVariable {
+                key: "GREETING".to_owned(),
+                value: "it's \"quoted\"".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::EnvVar))
+            },
+            GitLabVariable {
+                key: "MULTILINE".to_owned(),
+                value: "first\nsecond".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::EnvVar))
+            },
+            GitLabVariable {
+                key: "CERT".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::File))
+            },
+        ];
+        let cmd = DotEnvCommand {
+            format: OutputFormat::Docker,
+            container_folder: Some("/run/secrets".to_owned()),
+            ..gen_dotenv_command(None)
+        };
+        assert_eq!(
+            generate_output(&cmd, &variables),
+            Ok(vec!["GREETING=it's \"quoted\"".to_owned(), "CERT=/run/secrets/CERT.var".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_generate_output_in_kubernetes_formats() {
+        let variables = [
+            GitLabVariable {
+                key: "GREETING".to_owned(),
+                value: "hello".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::EnvVar))
+            },
+            GitLabVariable {
+                key: "CERT".to_owned(),
+                value: "certificate".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::File))
+            },
+        ];
+        let cmd = DotEnvCommand {
+            format: OutputFormat::Secret,
+            kubernetes: KubernetesMetadata {
+                name: "app".to_owned(),
+                ..KubernetesMetadata::default()
+            },
+            ..gen_dotenv_command(None)
+        };
+        let manifest = |cmd: &DotEnvCommand| serde_yaml::from_str::<serde_json::Value>(&generate_output(cmd, &variables).unwrap().concat()).unwrap();
+        assert_eq!(
+            manifest(&cmd),
+            serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Secret",
+                "metadata": {"name": "app"},
+                "type": "Opaque",
+                "data": {"GREETING": "aGVsbG8=", "CERT.var": "Y2VydGlmaWNhdGU="}
+            })
+        );
+        let config_map = DotEnvCommand {
+            format: OutputFormat::ConfigMap,
+            ..cmd
+        };
+        assert_eq!(manifest(&config_map)["data"], serde_json::json!({"GREETING": "hello", "CERT.var": "certificate"}));
+    }
+
+    #[test]
+    fn test_generate_output_in_systemd_format() {
+        let variables = [
+            GitLabVariable {
+                key: "GREETING".to_owned(),
+                value: "say \"hi\"\nto $USER".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::EnvVar))
+            },
+            GitLabVariable {
+                key: "CERT".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::File))
+            },
+        ];
+        let cmd = DotEnvCommand {
+            format: OutputFormat::Systemd,
+            folder: "/etc/my-app".to_owned(),
+            ..gen_dotenv_command(None)
+        };
+        assert_eq!(
+            generate_output(&cmd, &variables),
+            Ok(vec!["GREETING=\"say \\\"hi\\\"\nto \\$USER\"".to_owned(), "CERT=\"/etc/my-app/CERT.var\"".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_generate_output_in_direnv_format() {
+        let variables = [
+            gen_variable(Some(GitLabVariableType::EnvVar)),
+            GitLabVariable {
+                key: "CERT".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::File))
+            },
+        ];
+        let cmd = DotEnvCommand {
+            gitlab_project: GitLabProject {
+                name: encode("group/my-project"),
+                variables: vec![],
+            },
+            environment: "production".to_owned(),
+            format: OutputFormat::Direnv,
+            folder: ".env.production".to_owned(),
+            with_group_vars: true,
+            filter: VariableFilter {
+                protected: Some(false),
+                exclude_masked: false,
+            },
+            profile: Some("work".to_owned()),
+            url: "https://gitlab.example.com".to_owned(),
+            ..gen_dotenv_command(None)
+        };
+        assert_eq!(
+            generate_output(&cmd, &variables),
+            Ok(vec![
+                "# Generated by gitlab-rescue. Variables of project group/my-project are loaded from GitLab each time direnv loads this file.".to_owned(),
+                "watch_file '.gitlab-rescue.toml'".to_owned(),
+                "eval \"$(gitlab-rescue dotenv 'group/my-project' --environment='production' --folder='.env.production' --url='https://gitlab.example.com' --format=shell --shell=bash --profile='work' --with-group-vars --exclude-protected)\"".to_owned()
+            ])
+        );
+    }
 }
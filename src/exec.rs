@@ -0,0 +1,252 @@
+use crate::app_error::{
+    AppError::{Cli, InvalidInput},
+    Result,
+};
+use crate::dotenv::{get_expanded_list_of_variables, get_files_to_create, DotEnvCommand};
+use crate::gitlab_api::{GitLabVariable, GitLabVariableType};
+use crate::io::IO;
+use crate::{app_info, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Child, Command, ExitStatus};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Arguments for `exec` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecCommand {
+    /// Arguments for getting variables of the project, as `dotenv` command does
+    variables: DotEnvCommand,
+    /// Program to run and its arguments
+    command: Vec<String>,
+}
+
+impl Performable for ExecCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            let folder = create_private_folder()?;
+            let result = run_with_variables(&self, &folder.to_string_lossy());
+            let removed = fs::remove_dir_all(&folder).map_err(|e| Cli(format!("Folder {} could not be removed. Error: {}", folder.display(), e)));
+            let code = result?;
+            removed?;
+            match code {
+                0 => Ok(()),
+                code => process::exit(code),
+            }
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for ExecCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        ExecCommand {
+            variables: DotEnvCommand::from(argm),
+            command: argm.values_of("COMMAND").unwrap().map(|v| v.to_owned()).collect(),
+        }
+    }
+}
+
+/// Gets the variables of the project, writes variables of type "File" in `folder` and runs the command with the
+/// variables in its environment. Returns the exit code of the command.
+///
+/// # Arguments
+///
+/// * `cmd`    - Exec command
+/// * `folder` - Private folder where variables of type "File" are written
+///
+fn run_with_variables(cmd: &ExecCommand, folder: &str) -> Result<i32> {
+    let dotenv = cmd.variables.with_folder(folder);
+    app_info!("Getting variables from project {} in environment {}...", dotenv.project_name(), dotenv.environment());
+    let variables = get_expanded_list_of_variables(&dotenv)?;
+    get_files_to_create(folder, &variables)
+        .into_iter()
+        .try_fold((), |_, (file, content)| -> Result<()> { Ok(create_private_file(&file)?.write_all(&content)?) })
+        .map_err(|e| Cli(format!("Some files could not be created. Error: {}", e)))?;
+    app_info!("Running {} with {} variable(s)...", cmd.command[0], variables.len());
+    let child = Command::new(&cmd.command[0])
+        .args(&cmd.command[1..])
+        .envs(child_environment(&variables, folder))
+        .spawn()
+        .map_err(|e| InvalidInput(format!("Command {} could not be run. Error: {}", cmd.command[0], e)))?;
+    wait_forwarding_signals(child).map(exit_code)
+}
+
+/// Returns the variables to add to the environment of the command. Variables of type "File" point to their file.
+///
+/// # Arguments
+///
+/// * `variables` - List of GitLab variables
+/// * `folder`    - Folder where variables of type "File" are written
+///
+fn child_environment(variables: &[GitLabVariable], folder: &str) -> Vec<(String, String)> {
+    variables
+        .iter()
+        .map(|v| match v.variable_type {
+            GitLabVariableType::File => (v.key.clone(), format!("{}/{}.var", folder, v.key)),
+            GitLabVariableType::EnvVar => (v.key.clone(), v.value.clone()),
+        })
+        .collect()
+}
+
+/// Creates a new temporary folder, accessible only by its owner on Unix systems
+fn create_private_folder() -> Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    let folder = env::temp_dir().join(format!("gitlab-rescue-{}-{}", process::id(), nanos));
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(&folder)
+        .map_err(|e| Cli(format!("Folder {} could not be created. Error: {}", folder.display(), e)))?;
+    Ok(folder)
+}
+
+/// Creates a file, readable only by its owner on Unix systems since it contains secrets
+///
+/// # Arguments
+///
+/// * `file` - Path of the file
+///
+fn create_private_file(file: &str) -> std::io::Result<fs::File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(file)
+}
+
+/// Waits for the command to exit, sending it the signals received meanwhile (SIGHUP, SIGTERM, SIGUSR1 and SIGUSR2).
+/// SIGINT and SIGQUIT sent from the terminal already reach the command, since it runs in the same process group, so
+/// they are only caught to keep waiting for the command. Signals are sent before checking whether the command has
+/// exited, so they never reach a process that has already been reaped.
+///
+/// # Arguments
+///
+/// * `child` - Running command
+///
+#[cfg(unix)]
+fn wait_forwarding_signals(mut child: Child) -> Result<ExitStatus> {
+    use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+    use signal_hook::iterator::Signals;
+    let mut signals = Signals::new([SIGHUP, SIGINT, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2])?;
+    let pid = child.id() as libc::pid_t;
+    let status = loop {
+        signals.pending().filter(|s| *s != SIGINT && *s != SIGQUIT).for_each(|signal| unsafe {
+            libc::kill(pid, signal);
+        });
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
+            Err(e) => break Err(e),
+        }
+    };
+    signals.handle().close();
+    Ok(status?)
+}
+
+/// Waits for the command to exit. Console signals are received by the command itself on non-Unix systems.
+///
+/// # Arguments
+///
+/// * `child` - Running command
+///
+#[cfg(not(unix))]
+fn wait_forwarding_signals(mut child: Child) -> Result<ExitStatus> {
+    Ok(child.wait()?)
+}
+
+/// Returns the exit code of a command. As shells do, commands killed by a signal exit with 128 + signal number.
+///
+/// # Arguments
+///
+/// * `status` - Exit status of the command
+///
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::gen_variable;
+    use httpmock::MockServer;
+
+    fn exec_command(args: Vec<&str>) -> ExecCommand {
+        app()
+            .get_matches_from([vec!["gitlab-rescue", "exec"], args].concat())
+            .subcommand_matches("exec")
+            .map(ExecCommand::from)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_should_create_exec_cmd_from_cli_args() {
+        let cmd = exec_command(vec!["-p", "group/a-project", "-e", "staging", "--", "cargo", "run"]);
+        assert_eq!((cmd.variables.project_name(), cmd.variables.environment()), ("group%2Fa-project", "staging"));
+        assert_eq!(cmd.command, vec!["cargo".to_owned(), "run".to_owned()]);
+    }
+
+    #[test]
+    fn test_child_environment() {
+        let (env_variable, file_variable) = (gen_variable(Some(GitLabVariableType::EnvVar)), gen_variable(Some(GitLabVariableType::File)));
+        assert_eq!(
+            child_environment(&[env_variable.clone(), file_variable.clone()], "/tmp/x"),
+            vec![
+                (env_variable.key, env_variable.value),
+                (file_variable.key.clone(), format!("/tmp/x/{}.var", file_variable.key))
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_run_command_with_variables() {
+        let server = MockServer::start();
+        let variables = vec![
+            GitLabVariable {
+                key: "GREETING".to_owned(),
+                value: "hello".to_owned(),
+                environment_scope: "*".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::EnvVar))
+            },
+            GitLabVariable {
+                key: "CERT".to_owned(),
+                value: "certificate".to_owned(),
+                environment_scope: "*".to_owned(),
+                ..gen_variable(Some(GitLabVariableType::File))
+            },
+        ];
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/api/v4/projects/a-project/variables");
+            then.status(200).header("x-total", "2").json_body_obj(&variables);
+        });
+        let script = "test \"$GREETING\" = hello && test \"$(cat \"$CERT\")\" = certificate && exit 7";
+        let cmd = exec_command(vec!["-p", "a-project", "-u", &server.base_url(), "-t", "token", "--", "sh", "-c", script]);
+        let folder = create_private_folder().unwrap();
+        assert_eq!(run_with_variables(&cmd, &folder.to_string_lossy()), Ok(7));
+        fs::remove_dir_all(&folder).unwrap();
+        mock.assert();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_should_wait_for_the_command_to_exit() {
+        let child = Command::new("sh").args(["-c", "sleep 0.2; exit 3"]).spawn().unwrap();
+        assert_eq!(wait_forwarding_signals(child).map(exit_code), Ok(3));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exit_code_of_killed_command() {
+        let status = Command::new("sh").args(["-c", "kill -TERM $$"]).status().unwrap();
+        assert_eq!(exit_code(status), 128 + libc::SIGTERM);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::gitlab_api::{GitLabVariable, GitLabVariableType};
+use std::collections::HashMap;
+
+/// Expands `$NAME` and `${NAME}` references inside the values of non-raw variables of type env_var, as GitLab does
+/// in CI/CD jobs. Contents of variables of type File are kept as they are.
+/// References are resolved against `variables` themselves, `$$` is replaced by a literal `$`, and references
+/// to raw variables are replaced by their value without expanding it. References to unknown variables and
+/// references that lead to a cycle are left as they are. Returns the expanded variables and a warning for
+/// each reference that could not be expanded.
+///
+/// # Arguments
+///
+/// * `variables` - Resolved list of GitLab variables (one per key)
+/// * `folder`    - Folder where variables of type "File" are located. If present, references to these
+///   variables are replaced by the path of their file instead of their content.
+///
+pub(crate) fn expand_variables(variables: Vec<GitLabVariable>, folder: Option<&str>) -> (Vec<GitLabVariable>, Vec<String>) {
+    let index = variables.iter().map(|v| (v.key.as_str(), v)).collect::<HashMap<&str, &GitLabVariable>>();
+    let mut warnings = vec![];
+    let expanded = variables
+        .iter()
+        .map(|v| match v.raw || matches!(v.variable_type, GitLabVariableType::File) {
+            true => v.clone(),
+            _ => GitLabVariable {
+                value: expand_value(&v.value, &index, folder, &mut vec![v.key.clone()], &mut warnings),
+                ..v.clone()
+            },
+        })
+        .collect();
+    (expanded, warnings)
+}
+
+/// Returns `value` with its references expanded
+///
+/// # Arguments
+///
+/// * `value`     - Value to expand
+/// * `index`     - Variables by key
+/// * `folder`    - Folder where variables of type "File" are located
+/// * `stack`     - Keys of the variables being expanded, from the outermost one
+/// * `warnings`  - Warnings about references that could not be expanded
+///
+fn expand_value(value: &str, index: &HashMap<&str, &GitLabVariable>, folder: Option<&str>, stack: &mut Vec<String>, warnings: &mut Vec<String>) -> String {
+    let chars = value.chars().collect::<Vec<char>>();
+    let (mut expanded, mut i) = (String::new(), 0);
+    while i < chars.len() {
+        match (chars[i], reference_at(&chars, i)) {
+            ('$', _) if chars.get(i + 1) == Some(&'$') => {
+                expanded.push('$');
+                i += 2;
+            }
+            ('$', Some((name, end))) => {
+                let text = chars[i..end].iter().collect::<String>();
+                expanded.push_str(&expand_reference(&name, &text, index, folder, stack, warnings));
+                i = end;
+            }
+            (c, _) => {
+                expanded.push(c);
+                i += 1;
+            }
+        }
+    }
+    expanded
+}
+
+/// Returns the value of a single reference, or its original `text` if it can not be expanded
+fn expand_reference(name: &str, text: &str, index: &HashMap<&str, &GitLabVariable>, folder: Option<&str>, stack: &mut Vec<String>, warnings: &mut Vec<String>) -> String {
+    match index.get(name) {
+        None => {
+            warnings.push(format!("Variable {} references unknown variable {}", stack[0], name));
+            text.to_owned()
+        }
+        Some(_) if stack.iter().any(|k| k == name) => {
+            warnings.push(format!("Variable {} has a reference cycle: {} -> {}", stack[0], stack.join(" -> "), name));
+            text.to_owned()
+        }
+        Some(v) => match (v.variable_type, folder) {
+            (GitLabVariableType::File, Some(f)) => format!("{}/{}.var", f, v.key),
+            _ if v.raw => v.value.clone(),
+            _ => {
+                stack.push(name.to_owned());
+                let expanded = expand_value(&v.value, index, folder, stack, warnings);
+                stack.pop();
+                expanded
+            }
+        },
+    }
+}
+
+/// Returns the name of the variable referenced at position `start` of `chars` (`$NAME` or `${NAME}`) and the
+/// position right after the reference
+fn reference_at(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let is_name_char = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    match chars.get(start + 1) {
+        Some('{') => {
+            let end = start + 2 + chars[start + 2..].iter().position(|c| *c == '}')?;
+            let name = chars[start + 2..end].iter().collect::<String>();
+            match name.chars().next() {
+                Some(c) if (c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| is_name_char(&c)) => Some((name, end + 1)),
+                _ => None,
+            }
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let len = chars[start + 1..].iter().take_while(|c| is_name_char(c)).count();
+            Some((chars[start + 1..start + 1 + len].iter().collect(), start + 1 + len))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab_api::tests::gen_variable;
+
+    fn gen_expandable_variable(key: &str, value: &str, raw: bool) -> GitLabVariable {
+        GitLabVariable {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            raw,
+            variable_type: GitLabVariableType::EnvVar,
+            ..gen_variable(None)
+        }
+    }
+
+    fn values(variables: Vec<GitLabVariable>, folder: Option<&str>) -> (Vec<String>, Vec<String>) {
+        let (expanded, warnings) = expand_variables(variables, folder);
+        (expanded.into_iter().map(|v| v.value).collect(), warnings)
+    }
+
+    #[test]
+    fn test_expand_references() {
+        let variables = vec![
+            gen_expandable_variable("DATABASE_URL", "postgres://$DB_USER@${DB_HOST}:5432/db", false),
+            gen_expandable_variable("DB_USER", "admin", false),
+            gen_expandable_variable("DB_HOST", "$REGION.db.internal", false),
+            gen_expandable_variable("REGION", "eu", false),
+            gen_expandable_variable("PRICE", "$$5 for ${ not a reference} $1", false),
+        ];
+        assert_eq!(
+            values(variables, None),
+            (
+                vec![
+                    "postgres://admin@eu.db.internal:5432/db".to_owned(),
+                    "admin".to_owned(),
+                    "eu.db.internal".to_owned(),
+                    "eu".to_owned(),
+                    "$5 for ${ not a reference} $1".to_owned()
+                ],
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_raw_variables_are_not_expanded() {
+        let variables = vec![
+            gen_expandable_variable("RAW", "$PLAIN and $$", true),
+            gen_expandable_variable("PLAIN", "value with $RAW", false),
+        ];
+        assert_eq!(values(variables, None), (vec!["$PLAIN and $$".to_owned(), "value with $PLAIN and $$".to_owned()], vec![]));
+    }
+
+    #[test]
+    fn test_unknown_references_are_kept_with_a_warning() {
+        let variables = vec![gen_expandable_variable("A", "${UNKNOWN}-$UNKNOWN", false)];
+        let (expanded, warnings) = values(variables, None);
+        assert_eq!(expanded, vec!["${UNKNOWN}-$UNKNOWN".to_owned()]);
+        assert_eq!(warnings, vec!["Variable A references unknown variable UNKNOWN"; 2]);
+    }
+
+    #[test]
+    fn test_reference_cycles_are_detected() {
+        let variables = vec![
+            gen_expandable_variable("A", "a-$B", false),
+            gen_expandable_variable("B", "b-$A", false),
+            gen_expandable_variable("SELF", "$SELF", false),
+        ];
+        let (expanded, warnings) = values(variables, None);
+        assert_eq!(expanded, vec!["a-b-$A".to_owned(), "b-a-$B".to_owned(), "$SELF".to_owned()]);
+        assert_eq!(
+            warnings,
+            vec![
+                "Variable A has a reference cycle: A -> B -> A",
+                "Variable B has a reference cycle: B -> A -> B",
+                "Variable SELF has a reference cycle: SELF -> SELF"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_references_expand_to_file_path() {
+        let variables = vec![
+            gen_expandable_variable("CONFIG", "--config=$CERT", false),
+            GitLabVariable {
+                variable_type: GitLabVariableType::File,
+                ..gen_expandable_variable("CERT", "certificate", false)
+            },
+        ];
+        assert_eq!(values(variables.clone(), Some(".env.A")).0[0], "--config=.env.A/CERT.var");
+        let file = GitLabVariable {
+            variable_type: GitLabVariableType::File,
+            ..gen_expandable_variable("SCRIPT", "echo $HOME $CERT", false)
+        };
+        assert_eq!(values([variables.clone(), vec![file]].concat(), None).0[2], "echo $HOME $CERT");
+        assert_eq!(values(variables, None).0[0], "--config=certificate");
+    }
+}
@@ -10,6 +10,10 @@ pub mod tests {
         let mut rng = thread_rng();
         (options[rng.gen_range(0..options.len())] as char).to_string()
     }
+    pub fn gen_string_from(options: &[char], len: usize) -> String {
+        let mut rng = thread_rng();
+        (0..len).map(|_| options[rng.gen_range(0..options.len())]).collect()
+    }
     pub fn gen_bool() -> bool {
         thread_rng().gen_range(0..1) != 0
     }
@@ -1,8 +1,12 @@
 use crate::api_client::{api_client, DEFAULT_ENVIRONMENT};
-use crate::app_error::Result;
-use crate::gitlab_api::GitLabApi;
+use crate::app_error::{AppError::NotFound, Result};
+use crate::config::{project_or_group, Profile};
+use crate::dotenv::{all_from_api, RequestConfig};
+use crate::expand::expand_variables;
+use crate::gitlab_api::{GitLabApi, GitLabVariable, GitLabVariableType, VariableSource};
 use crate::io::IO;
-use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use crate::scope::resolve_variables;
+use crate::{app_info, app_success, app_warning, extract_environment, extract_token, extract_url, Performable};
 use clap::ArgMatches;
 use std::convert::From;
 use std::env;
@@ -17,6 +21,8 @@ pub struct GetVariableCommand {
     gitlab_project: Option<String>,
     /// Group ID or URL-encoded path of the group
     gitlab_group: Option<String>,
+    /// Get variable from GitLab instance
+    instance: bool,
     /// Name of GitLab CI/CD environment
     environment: String,
     /// If variable is not found in defined environment (-e option), try with "All" environment.
@@ -31,13 +37,15 @@ impl Performable for GetVariableCommand {
     fn get_action(self) -> IO<Result<()>> {
         IO::unit(move || {
             app_info!("Getting variable {} from GitLab API...", &self.name);
-            match self.gitlab_project.as_ref() {
-                Some(_) => Ok(get_variable_from_project(&self)?),
-                None => Ok(get_variable_from_group(&self)?),
+            match (self.gitlab_project.as_ref(), self.gitlab_group.as_ref()) {
+                (Some(_), _) => Ok(get_variable_from_project(&self)?),
+                (_, Some(_)) => Ok(get_variable_from_group(&self)?),
+                _ => Ok(get_variable_from_instance(&self)?),
             }
+            .and_then(|v| expand_variable(&self, v))
             .map(|v| {
-                app_success!("Variable {} obtained successfully", self.name);
-                println!("{}", v)
+                app_success!("Variable {} obtained successfully from scope {}{}", self.name, v.environment_scope, v.details());
+                println!("{}", v.value)
             })
         })
     }
@@ -45,43 +53,93 @@ impl Performable for GetVariableCommand {
 
 impl From<&ArgMatches<'_>> for GetVariableCommand {
     fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        let (project, group) = project_or_group(argm, &profile);
         GetVariableCommand {
             name: argm.value_of("VARIABLE_NAME").unwrap().to_owned(),
-            gitlab_project: argm.value_of("project").map(|p| encode(p)),
-            gitlab_group: argm.value_of("group").map(|g| g.to_owned()),
-            environment: argm.value_of("environment").map_or_else(|| "All".to_owned(), |v| v.to_owned()),
+            gitlab_project: project.as_deref().map(encode),
+            gitlab_group: group.as_deref().map(encode),
+            instance: argm.is_present("instance"),
+            environment: extract_environment!(argm, profile),
             from_all_if_missing: argm.is_present("from-all-if-missing"),
-            url: extract_url!(argm),
-            token: extract_token!(argm),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
         }
     }
 }
 
-/// Returns the variable value obtained from GitLab API in specified `[group]`
-fn get_variable_from_group(cmd: &GetVariableCommand) -> Result<String> {
+/// Returns the variable obtained from GitLab API in specified `[group]`
+fn get_variable_from_group(cmd: &GetVariableCommand) -> Result<GitLabVariable> {
     api_client(&cmd.url, &cmd.token)
-        .get_from_group(cmd.gitlab_group.as_ref().unwrap(), &cmd.name)
-        .map(|g| g.value)
+        .get_from_group(cmd.gitlab_group.as_ref().unwrap(), &cmd.name, &cmd.environment)
+        .or_else(|e| match matches!(e, NotFound(_)) && cmd.environment != DEFAULT_ENVIRONMENT {
+            true => get_variable_from_wildcard_scope(cmd, VariableSource::Group(cmd.gitlab_group.clone().unwrap())),
+            _ => Err(e),
+        })
+        .or_else(|e| match matches!(e, NotFound(_)) && cmd.environment != DEFAULT_ENVIRONMENT && cmd.from_all_if_missing {
+            true => api_client(&cmd.url, &cmd.token).get_from_group(cmd.gitlab_group.as_ref().unwrap(), &cmd.name, DEFAULT_ENVIRONMENT),
+            _ => Err(e),
+        })
+}
+
+/// Returns the variable obtained from GitLab API in GitLab instance
+fn get_variable_from_instance(cmd: &GetVariableCommand) -> Result<GitLabVariable> {
+    api_client(&cmd.url, &cmd.token).get_from_instance(&cmd.name)
 }
 
-/// Returns the variable value obtained from GitLab API in specified `[project]`
-fn get_variable_from_project(cmd: &GetVariableCommand) -> Result<String> {
+/// Returns the variable obtained from GitLab API in specified `[project]`
+fn get_variable_from_project(cmd: &GetVariableCommand) -> Result<GitLabVariable> {
     api_client(&cmd.url, &cmd.token)
         .get_from_project(cmd.gitlab_project.as_ref().unwrap(), &cmd.name, &cmd.environment)
-        .map(|g| g.value)
-        .or_else(|e| match cmd.environment != DEFAULT_ENVIRONMENT && cmd.from_all_if_missing {
-            true => api_client(&cmd.url, &cmd.token)
-                .get_from_project(cmd.gitlab_project.as_ref().unwrap(), &cmd.name, DEFAULT_ENVIRONMENT)
-                .map(|g| g.value),
+        .or_else(|e| match matches!(e, NotFound(_)) && cmd.environment != DEFAULT_ENVIRONMENT {
+            true => get_variable_from_wildcard_scope(cmd, VariableSource::Project(cmd.gitlab_project.clone().unwrap())),
             _ => Err(e),
         })
+        .or_else(|e| match matches!(e, NotFound(_)) && cmd.environment != DEFAULT_ENVIRONMENT && cmd.from_all_if_missing {
+            true => api_client(&cmd.url, &cmd.token).get_from_project(cmd.gitlab_project.as_ref().unwrap(), &cmd.name, DEFAULT_ENVIRONMENT),
+            _ => Err(e),
+        })
+}
+
+/// Returns the variable defined in the most specific wildcard environment scope (e.g. `review/*`) that matches
+/// the specified environment. GitLab API can only filter by exact scope, so variables of the project or group are
+/// listed.
+fn get_variable_from_wildcard_scope(cmd: &GetVariableCommand, source: VariableSource) -> Result<GitLabVariable> {
+    let variables = all_from_api(RequestConfig::new(&cmd.url, &cmd.token, source, 1, 100), num_cpus::get())?
+        .into_iter()
+        .filter(|v| v.key == cmd.name && v.environment_scope != DEFAULT_ENVIRONMENT)
+        .collect();
+    resolve_variables(variables, &cmd.environment)
+        .pop()
+        .ok_or_else(|| NotFound(format!("Variable {} not found in environment {}", cmd.name, cmd.environment)))
+}
+
+/// Returns the variable with references to other variables of the same project, group or instance expanded
+fn expand_variable(cmd: &GetVariableCommand, variable: GitLabVariable) -> Result<GitLabVariable> {
+    if variable.raw || matches!(variable.variable_type, GitLabVariableType::File) || !variable.value.contains('$') {
+        return Ok(variable);
+    }
+    let source = match (cmd.gitlab_project.as_ref(), cmd.gitlab_group.as_ref()) {
+        (Some(p), _) => VariableSource::Project(p.clone()),
+        (_, Some(g)) => VariableSource::Group(g.clone()),
+        _ => VariableSource::Instance,
+    };
+    let others = resolve_variables(all_from_api(RequestConfig::new(&cmd.url, &cmd.token, source, 1, 100), num_cpus::get())?, &cmd.environment)
+        .into_iter()
+        .filter(|v| v.key != variable.key)
+        .collect::<Vec<GitLabVariable>>();
+    let (expanded, warnings) = expand_variables([vec![variable], others].concat(), None);
+    warnings.iter().for_each(|w| app_warning!("{}", w));
+    Ok(expanded.into_iter().next().unwrap())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::app_error::AppError::Api;
     use crate::clap_app::app;
     use crate::gitlab_api::tests::*;
+    use crate::gitlab_api::GitLabVariable;
     use httpmock::MockServer;
 
     fn gen_getvar_command(url: &str, from_all_if_missing: bool, gitlab_project: Option<String>) -> GetVariableCommand {
@@ -93,6 +151,7 @@ mod tests {
                 Some(GEN_GROUP_NAME.clone())
             },
             gitlab_project,
+            instance: false,
             environment: GEN_ENVIRONMENT.clone(),
             from_all_if_missing,
             url: url.to_owned(),
@@ -122,7 +181,20 @@ mod tests {
     fn test_should_get_variable_from_group() {
         let server = MockServer::start();
         let mock = server.mock(httpmock_group_variable());
-        assert!(get_variable_from_group(&gen_getvar_command(&server.base_url(), false, None)).map_or_else(|_| false, |v| v == GEN_GITLAB_VARIABLE.value));
+        assert!(get_variable_from_group(&gen_getvar_command(&server.base_url(), false, None)).map_or_else(|_| false, |v| v.value == GEN_GITLAB_VARIABLE.value));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_variable_from_instance() {
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_instance_variable());
+        let cmd = GetVariableCommand {
+            gitlab_group: None,
+            instance: true,
+            ..gen_getvar_command(&server.base_url(), false, None)
+        };
+        assert_eq!(get_variable_from_instance(&cmd), Ok(GEN_GITLAB_VARIABLE_ALL.clone()));
         mock.assert();
     }
 
@@ -130,14 +202,145 @@ mod tests {
     fn test_should_get_variable_from_project() {
         let server = MockServer::start();
         let mut mock = server.mock(httpmock_project_variable(GEN_GITLAB_VARIABLE.environment_scope.clone()));
-        assert!(
-            get_variable_from_project(&gen_getvar_command(&server.base_url(), false, Some(GEN_PROJECT_NAME.to_owned()))).map_or_else(|_| false, |v| v == GEN_GITLAB_VARIABLE.value)
-        );
+        assert!(get_variable_from_project(&gen_getvar_command(&server.base_url(), false, Some(GEN_PROJECT_NAME.to_owned())))
+            .map_or_else(|_| false, |v| v.value == GEN_GITLAB_VARIABLE.value));
         mock.assert();
         mock.delete();
         let mock = server.mock(httpmock_project_variable("*".to_owned()));
         assert!(get_variable_from_project(&gen_getvar_command(&server.base_url(), true, Some(GEN_PROJECT_NAME.to_owned())))
-            .map_or_else(|_| false, |v| v == GEN_GITLAB_VARIABLE_ALL.value));
+            .map_or_else(|_| false, |v| v.value == GEN_GITLAB_VARIABLE_ALL.value));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_variable_from_wildcard_scope() {
+        let server = MockServer::start();
+        let variable = GitLabVariable {
+            key: GEN_NAME.clone(),
+            environment_scope: "review/feature-*".to_owned(),
+            ..gen_variable(None)
+        };
+        let list = vec![
+            gen_variable(None),
+            GitLabVariable {
+                key: GEN_NAME.clone(),
+                environment_scope: "*".to_owned(),
+                ..gen_variable(None)
+            },
+            GitLabVariable {
+                key: GEN_NAME.clone(),
+                environment_scope: "review/*".to_owned(),
+                ..gen_variable(None)
+            },
+            variable.clone(),
+        ];
+        let mock = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}/variables", *GEN_PROJECT_NAME));
+            then.status(200).header("x-total", "4").json_body_obj(&list);
+        });
+        let cmd = GetVariableCommand {
+            environment: "review/feature-x".to_owned(),
+            ..gen_getvar_command(&server.base_url(), false, Some(GEN_PROJECT_NAME.to_owned()))
+        };
+        assert_eq!(get_variable_from_project(&cmd), Ok(variable));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_variable_from_group_wildcard_scope() {
+        let server = MockServer::start();
+        let variable = GitLabVariable {
+            key: GEN_NAME.clone(),
+            environment_scope: "review/*".to_owned(),
+            ..gen_variable(None)
+        };
+        let list = vec![
+            GitLabVariable {
+                key: GEN_NAME.clone(),
+                environment_scope: "*".to_owned(),
+                ..gen_variable(None)
+            },
+            variable.clone(),
+        ];
+        let exact = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}/variables/{}", *GEN_GROUP_NAME, *GEN_NAME));
+            then.status(404);
+        });
+        let mock = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}/variables", *GEN_GROUP_NAME));
+            then.status(200).header("x-total", "2").json_body_obj(&list);
+        });
+        let cmd = GetVariableCommand {
+            environment: "review/feature-x".to_owned(),
+            ..gen_getvar_command(&server.base_url(), false, None)
+        };
+        assert_eq!(get_variable_from_group(&cmd), Ok(variable));
+        exact.assert();
         mock.assert();
     }
+
+    #[test]
+    fn test_should_not_fall_back_if_variable_could_not_be_requested() {
+        let server = MockServer::start();
+        let exact = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, *GEN_NAME));
+            then.status(403);
+        });
+        let mock = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}/variables", *GEN_PROJECT_NAME));
+            then.status(200).header("x-total", "1").json_body_obj(&vec![GEN_GITLAB_VARIABLE_ALL.clone()]);
+        });
+        let cmd = GetVariableCommand {
+            environment: "review/feature-x".to_owned(),
+            ..gen_getvar_command(&server.base_url(), true, Some(GEN_PROJECT_NAME.to_owned()))
+        };
+        assert!(matches!(get_variable_from_project(&cmd), Err(Api(_))));
+        exact.assert();
+        mock.assert_hits(0);
+    }
+
+    #[test]
+    fn get_should_encode_group_from_cli_args() {
+        app()
+            .get_matches_from(vec!["gitlab-rescue", "get", &GEN_NAME, "-g=parent/child", "-u=gitlab.com", "-t=a-token"])
+            .subcommand_matches("get")
+            .map(|a| assert_eq!(GetVariableCommand::from(a).gitlab_group, Some("parent%2Fchild".to_owned())))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_should_expand_references_of_variable() {
+        let server = MockServer::start();
+        let variable = GitLabVariable {
+            key: GEN_NAME.clone(),
+            value: "postgres://$DB_USER@db".to_owned(),
+            raw: false,
+            variable_type: GitLabVariableType::EnvVar,
+            ..GEN_GITLAB_VARIABLE.clone()
+        };
+        let list = vec![
+            variable.clone(),
+            GitLabVariable {
+                key: "DB_USER".to_owned(),
+                value: "admin".to_owned(),
+                environment_scope: "*".to_owned(),
+                raw: false,
+                ..gen_variable(None)
+            },
+        ];
+        let mock = server.mock(|when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}/variables", *GEN_PROJECT_NAME));
+            then.status(200).header("x-total", "2").json_body_obj(&list);
+        });
+        let cmd = gen_getvar_command(&server.base_url(), false, Some(GEN_PROJECT_NAME.to_owned()));
+        assert_eq!(expand_variable(&cmd, variable.clone()).map(|v| v.value), Ok("postgres://admin@db".to_owned()));
+        let raw = GitLabVariable { raw: true, ..variable.clone() };
+        assert_eq!(expand_variable(&cmd, raw.clone()), Ok(raw));
+        let file = GitLabVariable {
+            variable_type: GitLabVariableType::File,
+            ..variable
+        };
+        assert_eq!(expand_variable(&cmd, file.clone()), Ok(file));
+        mock.assert_hits(1);
+    }
 }
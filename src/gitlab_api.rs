@@ -1,6 +1,10 @@
 use crate::app_error::{AppError::Cli, Result};
-use reqwest::blocking::{Client as BlockingClient, Response as BlockingResponse};
+use reqwest::blocking::{Client as BlockingClient, RequestBuilder as BlockingRequestBuilder, Response as BlockingResponse};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::env;
+use urlencoding::encode;
 
 /// GitLab project infomation
 #[derive(Clone, Debug, PartialEq)]
@@ -11,6 +15,65 @@ pub struct GitLabProject {
     pub variables: Vec<GitLabVariable>,
 }
 
+/// GitLab namespace information of a project
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitLabNamespace {
+    /// Namespace kind. Available kinds are: user and group
+    pub kind: String,
+    /// Full path of the namespace (e.g. `group/subgroup`)
+    pub full_path: String,
+}
+
+impl GitLabNamespace {
+    /// Returns the full path of the namespace group and all its ancestors, from the closest group to the root group.
+    /// Returns an empty list if the namespace is not a group.
+    pub fn group_hierarchy(&self) -> Vec<String> {
+        match self.kind.as_str() {
+            "group" => {
+                let parts = self.full_path.split('/').collect::<Vec<&str>>();
+                (1..=parts.len()).rev().map(|i| parts[..i].join("/")).collect()
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// GitLab group information
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitLabGroup {
+    /// Group ID
+    pub id: u64,
+    /// Full path of the group (e.g. `group/subgroup`)
+    pub full_path: String,
+}
+
+/// GitLab project information returned when listing projects of a group
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitLabProjectSummary {
+    /// Project ID
+    pub id: u64,
+    /// NAMESPACE/PROJECT_NAME of the project
+    pub path_with_namespace: String,
+}
+
+/// GitLab project information returned by GitLab API
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct GitLabProjectResponse {
+    /// Namespace the project belongs to
+    namespace: GitLabNamespace,
+}
+
+/// Owner of a list of GitLab CI/CD variables
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariableSource {
+    /// Project ID or URL-encoded NAMESPACE/PROJECT_NAME
+    Project(String),
+    /// Group ID or URL-encoded path of the group
+    Group(String),
+    /// GitLab instance (self-managed GitLab only)
+    Instance,
+}
+
 /// GitLab variable type
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum GitLabVariableType {
@@ -32,10 +95,109 @@ pub struct GitLabVariable {
     /// The value of a variable
     pub value: String,
     /// Variable's environment
+    #[serde(default = "default_environment_scope")]
     pub environment_scope: String,
+    /// Whether the variable is only exported in protected branches and tags
+    #[serde(default)]
+    pub protected: bool,
+    /// Whether the variable is masked in job logs
+    #[serde(default)]
+    pub masked: bool,
+    /// Whether the variable is treated as a raw string (no variable expansion)
+    #[serde(default)]
+    pub raw: bool,
+    /// Description of the variable
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Environment scope of variables returned by GitLab API without scope (e.g. group variables in GitLab Free)
+fn default_environment_scope() -> String {
+    "*".to_owned()
+}
+
+/// Attributes of a variable to create or update in GitLab. Attributes set to `None` are not sent, so GitLab
+/// keeps their current value on update or uses its default on create.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GitLabVariableAttributes {
+    /// The key of the variable
+    pub key: String,
+    /// The value of a variable
+    pub value: String,
+    /// Variable's environment
+    pub environment_scope: String,
+    /// The type of a variable. Available types are: env_var and file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variable_type: Option<GitLabVariableType>,
+    /// Whether the variable is only exported in protected branches and tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
+    /// Whether the variable is masked in job logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub masked: Option<bool>,
+    /// Whether the variable is treated as a raw string (no variable expansion)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<bool>,
+    /// Description of the variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl GitLabVariableAttributes {
+    /// Returns the environment scope as expected by GitLab API
+    fn api_environment_scope(&self) -> &str {
+        if self.environment_scope == "All" {
+            "*"
+        } else {
+            &self.environment_scope
+        }
+    }
+
+    /// Clone [GitLabVariableAttributes](struct@GitLabVariableAttributes) object parsing `environment_scope` attribute
+    fn clone_for_request(&self) -> GitLabVariableAttributes {
+        GitLabVariableAttributes {
+            environment_scope: self.api_environment_scope().to_owned(),
+            ..self.clone()
+        }
+    }
+}
+
+impl From<&GitLabVariable> for GitLabVariableAttributes {
+    fn from(variable: &GitLabVariable) -> Self {
+        GitLabVariableAttributes {
+            key: variable.key.clone(),
+            value: variable.value.clone(),
+            environment_scope: variable.environment_scope.clone(),
+            variable_type: Some(variable.variable_type),
+            protected: Some(variable.protected),
+            masked: Some(variable.masked),
+            raw: Some(variable.raw),
+            description: variable.description.clone(),
+        }
+    }
 }
 
 impl GitLabVariable {
+    /// Returns the flags set in the variable and its description, ready to be appended to a message (e.g.
+    /// ` (protected, masked; Database password)`). Returns an empty string if there is nothing to show.
+    pub fn details(&self) -> String {
+        let flags = [("protected", self.protected), ("masked", self.masked), ("raw", self.raw)]
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(", ");
+        match [flags, self.description.clone().unwrap_or_default()]
+            .iter()
+            .filter(|p| !p.is_empty())
+            .cloned()
+            .collect::<Vec<String>>()
+        {
+            parts if parts.is_empty() => String::new(),
+            parts => format!(" ({})", parts.join("; ")),
+        }
+    }
+
     /// Clone [GitLabVariable](struct@GitLabVariable) object parsing `environment_scope` attribute
     fn clone_from_response(&self) -> GitLabVariable {
         GitLabVariable {
@@ -44,9 +206,7 @@ impl GitLabVariable {
             } else {
                 self.environment_scope.clone()
             },
-            variable_type: self.variable_type,
-            key: self.key.clone(),
-            value: self.value.clone(),
+            ..self.clone()
         }
     }
 }
@@ -57,9 +217,81 @@ pub trait GitLabApi {
     /// Get a variable value from a specific GitLab project
     fn get_from_project(&self, project: &str, name: &str, env: &str) -> Result<GitLabVariable>;
     /// Get a variable value from a specific GitLab group
-    fn get_from_group(&self, group: &str, name: &str) -> Result<GitLabVariable>;
+    fn get_from_group(&self, group: &str, name: &str, env: &str) -> Result<GitLabVariable>;
     /// List variables from a specific GitLab project
     fn list_from_project(&self, project: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)>;
+    /// List variables from a specific GitLab group
+    fn list_from_group(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)>;
+    /// Get a variable value from GitLab instance
+    fn get_from_instance(&self, name: &str) -> Result<GitLabVariable>;
+    /// List variables from GitLab instance
+    fn list_from_instance(&self, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)>;
+    /// Get the namespace a specific GitLab project belongs to
+    fn get_project_namespace(&self, project: &str) -> Result<GitLabNamespace>;
+    /// Create a variable in a specific GitLab project
+    fn create_in_project(&self, project: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable>;
+    /// Update a variable of a specific GitLab project in the variable's environment scope
+    fn update_in_project(&self, project: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable>;
+    /// Create a variable in a specific GitLab group
+    fn create_in_group(&self, group: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable>;
+    /// Update a variable of a specific GitLab group in the variable's environment scope
+    fn update_in_group(&self, group: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable>;
+    /// Delete a variable of a specific GitLab project in a specific environment scope
+    fn delete_from_project(&self, project: &str, name: &str, env: &str) -> Result<()>;
+    /// Delete a variable of a specific GitLab group in a specific environment scope
+    fn delete_from_group(&self, group: &str, name: &str, env: &str) -> Result<()>;
+    /// Get information of a specific GitLab group
+    fn get_group(&self, group: &str) -> Result<GitLabGroup>;
+    /// List direct subgroups of a specific GitLab group
+    fn list_subgroups(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabGroup>, usize)>;
+    /// List projects that belong directly to a specific GitLab group
+    fn list_group_projects(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabProjectSummary>, usize)>;
+}
+
+/// Authentication modes of GitLab API
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthMode {
+    /// Personal, project or group access token, sent in `PRIVATE-TOKEN` header
+    PrivateToken,
+    /// OAuth2 access token, sent in `Authorization: Bearer` header
+    OAuth,
+    /// CI/CD job token, sent in `JOB-TOKEN` header
+    JobToken,
+}
+
+impl AuthMode {
+    /// Returns the authentication mode of a token: [JobToken](AuthMode::JobToken) if it is the value of
+    /// `CI_JOB_TOKEN` variable, [OAuth](AuthMode::OAuth) if it is the value of `GITLAB_OAUTH_TOKEN` variable and
+    /// [PrivateToken](AuthMode::PrivateToken) otherwise
+    ///
+    /// # Arguments
+    ///
+    /// * `token`       - GitLab API token
+    /// * `job_token`   - Value of `CI_JOB_TOKEN` variable
+    /// * `oauth_token` - Value of `GITLAB_OAUTH_TOKEN` variable
+    ///
+    pub fn detect(token: &str, job_token: Option<&str>, oauth_token: Option<&str>) -> Self {
+        let is_token = |value: Option<&str>| !token.is_empty() && value == Some(token);
+        match (is_token(job_token), is_token(oauth_token)) {
+            (true, _) => AuthMode::JobToken,
+            (_, true) => AuthMode::OAuth,
+            _ => AuthMode::PrivateToken,
+        }
+    }
+
+    /// Returns the name and the value of the header that authenticates requests with `token`
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - GitLab API token
+    ///
+    pub fn header(&self, token: &str) -> (&'static str, String) {
+        match self {
+            AuthMode::PrivateToken => ("PRIVATE-TOKEN", token.to_owned()),
+            AuthMode::OAuth => ("Authorization", format!("Bearer {}", token)),
+            AuthMode::JobToken => ("JOB-TOKEN", token.to_owned()),
+        }
+    }
 }
 
 /// Implementation of [GitLabApi](trait@GitLabApi) v4
@@ -67,32 +299,89 @@ pub trait GitLabApi {
 pub struct GitLabApiV4 {
     url: String,
     token: String,
+    auth: AuthMode,
 }
 
-impl<'a> GitLabApi for GitLabApiV4 {
+impl GitLabApi for GitLabApiV4 {
     fn new(url: String, token: String) -> Self {
         GitLabApiV4 {
             url: format!("{}/api/v4", url),
+            auth: AuthMode::detect(&token, env::var("CI_JOB_TOKEN").ok().as_deref(), env::var("GITLAB_OAUTH_TOKEN").ok().as_deref()),
             token,
         }
     }
 
     fn get_from_project(&self, project: &str, name: &str, env: &str) -> Result<GitLabVariable> {
-        self.get(&format!(
-            "projects/{}/variables/{}?filter[environment_scope]={}",
-            project,
-            name,
-            if env == "All" { "*" } else { env }
-        ))
+        self.get(&format!("projects/{}/variables/{}?{}", project, name, environment_filter(env)))
     }
 
-    fn get_from_group(&self, group: &str, name: &str) -> Result<GitLabVariable> {
-        self.get(&format!("groups/{}/variables/{}", group, name))
+    fn get_from_group(&self, group: &str, name: &str, env: &str) -> Result<GitLabVariable> {
+        self.get(&format!("groups/{}/variables/{}?{}", group, name, environment_filter(env)))
     }
 
     fn list_from_project(&self, project: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)> {
         self.list(&format!("projects/{}/variables?page={}&per_page={}", project, page, per_page))
     }
+
+    fn list_from_group(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)> {
+        self.list(&format!("groups/{}/variables?page={}&per_page={}", group, page, per_page))
+    }
+
+    fn get_from_instance(&self, name: &str) -> Result<GitLabVariable> {
+        self.get(&format!("admin/ci/variables/{}", name))
+    }
+
+    fn list_from_instance(&self, page: usize, per_page: usize) -> Result<(Vec<GitLabVariable>, usize)> {
+        self.list(&format!("admin/ci/variables?page={}&per_page={}", page, per_page))
+    }
+
+    fn get_project_namespace(&self, project: &str) -> Result<GitLabNamespace> {
+        Ok(self.send(&format!("projects/{}", project))?.json::<GitLabProjectResponse>()?.namespace)
+    }
+
+    fn create_in_project(&self, project: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable> {
+        self.write(Method::POST, &format!("projects/{}/variables", project), variable)
+    }
+
+    fn update_in_project(&self, project: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable> {
+        self.write(
+            Method::PUT,
+            &format!("projects/{}/variables/{}?{}", project, variable.key, environment_filter(&variable.environment_scope)),
+            variable,
+        )
+    }
+
+    fn create_in_group(&self, group: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable> {
+        self.write(Method::POST, &format!("groups/{}/variables", group), variable)
+    }
+
+    fn update_in_group(&self, group: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable> {
+        self.write(
+            Method::PUT,
+            &format!("groups/{}/variables/{}?{}", group, variable.key, environment_filter(&variable.environment_scope)),
+            variable,
+        )
+    }
+
+    fn delete_from_project(&self, project: &str, name: &str, env: &str) -> Result<()> {
+        self.delete(&format!("projects/{}/variables/{}?{}", project, name, environment_filter(env)))
+    }
+
+    fn delete_from_group(&self, group: &str, name: &str, env: &str) -> Result<()> {
+        self.delete(&format!("groups/{}/variables/{}?{}", group, name, environment_filter(env)))
+    }
+
+    fn get_group(&self, group: &str) -> Result<GitLabGroup> {
+        Ok(self.send(&format!("groups/{}", group))?.json::<GitLabGroup>()?)
+    }
+
+    fn list_subgroups(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabGroup>, usize)> {
+        self.list_items(&format!("groups/{}/subgroups?page={}&per_page={}", group, page, per_page))
+    }
+
+    fn list_group_projects(&self, group: &str, page: usize, per_page: usize) -> Result<(Vec<GitLabProjectSummary>, usize)> {
+        self.list_items(&format!("groups/{}/projects?with_shared=false&page={}&per_page={}", group, page, per_page))
+    }
 }
 
 impl GitLabApiV4 {
@@ -103,34 +392,97 @@ impl GitLabApiV4 {
     /// * `endpoint` - GitLab API endpoint to consume
     ///
     fn get(&self, endpoint: &str) -> Result<GitLabVariable> {
-        Ok(BlockingClient::builder()
-            .build()?
-            .get(format!("{}/{}", self.url, endpoint))
-            .header("PRIVATE-TOKEN", &self.token)
+        Ok(self.send(endpoint)?.json::<GitLabVariable>()?.clone_from_response())
+    }
+
+    /// Return a list of [GitLabVariable](struct@GitLabVariable) objects from GitLabAPI
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - GitLab API endpoint to consume
+    ///
+    fn list(&self, endpoint: &str) -> Result<(Vec<GitLabVariable>, usize)> {
+        let (list, total) = self.list_items::<GitLabVariable>(endpoint)?;
+        Ok((list.iter().map(|v| v.clone_from_response()).collect(), total))
+    }
+
+    /// Return a page of items from GitLabAPI and the total number of items
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - GitLab API endpoint to consume
+    ///
+    fn list_items<T: DeserializeOwned>(&self, endpoint: &str) -> Result<(Vec<T>, usize)> {
+        let res = self.send(endpoint)?;
+        let total = get_pagination_header(&res, "x-total")?;
+        Ok((res.json::<Vec<T>>()?, total))
+    }
+
+    /// Create or update a variable through GitLab API and return the resulting [GitLabVariable](struct@GitLabVariable)
+    ///
+    /// # Arguments
+    ///
+    /// * `method`   - HTTP method (POST for creating, PUT for updating)
+    /// * `endpoint` - GitLab API endpoint to consume
+    /// * `variable` - Attributes of the variable
+    ///
+    fn write(&self, method: Method, endpoint: &str, variable: &GitLabVariableAttributes) -> Result<GitLabVariable> {
+        Ok(self
+            .request(method, endpoint)?
+            .json(&variable.clone_for_request())
             .send()?
             .error_for_status()?
             .json::<GitLabVariable>()?
             .clone_from_response())
     }
 
-    /// Return a list of [GitLabVariable](struct@GitLabVariable) objects from GitLabAPI
+    /// Delete a variable through GitLab API
     ///
     /// # Arguments
     ///
     /// * `endpoint` - GitLab API endpoint to consume
     ///
-    fn list(&self, endpoint: &str) -> Result<(Vec<GitLabVariable>, usize)> {
-        let res = BlockingClient::builder()
+    fn delete(&self, endpoint: &str) -> Result<()> {
+        self.request(Method::DELETE, endpoint)?.send()?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Send a GET request to GitLab API and return the response if its status is successful
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - GitLab API endpoint to consume
+    ///
+    fn send(&self, endpoint: &str) -> Result<BlockingResponse> {
+        Ok(self.request(Method::GET, endpoint)?.send()?.error_for_status()?)
+    }
+
+    /// Return an authenticated request to GitLab API
+    ///
+    /// # Arguments
+    ///
+    /// * `method`   - HTTP method
+    /// * `endpoint` - GitLab API endpoint to consume
+    ///
+    fn request(&self, method: Method, endpoint: &str) -> Result<BlockingRequestBuilder> {
+        let (header, value) = self.auth.header(&self.token);
+        Ok(BlockingClient::builder()
             .build()?
-            .get(format!("{}/{}", self.url, endpoint))
-            .header("PRIVATE-TOKEN", &self.token)
-            .send()?
-            .error_for_status()?;
-        let total = get_pagination_header(&res, "x-total")?;
-        Ok((res.json::<Vec<GitLabVariable>>()?.iter().map(|v| v.clone_from_response()).collect(), total))
+            .request(method, format!("{}/{}", self.url, endpoint))
+            .header(header, value))
     }
 }
 
+/// Return the query parameter that restricts a variable endpoint to a single environment scope
+///
+/// # Arguments
+///
+/// * `env` - Name of GitLab CI/CD environment scope ("All" is translated to `*`)
+///
+fn environment_filter(env: &str) -> String {
+    format!("filter[environment_scope]={}", encode(if env == "All" { "*" } else { env }))
+}
+
 /// Return numeric header from GitLab API response
 ///
 /// # Arguments
@@ -140,13 +492,14 @@ impl GitLabApiV4 {
 ///
 fn get_pagination_header(res: &BlockingResponse, header: &str) -> Result<usize> {
     match res.headers().get(header).and_then(|h| h.to_str().ok()) {
-        Some(h) if h.is_empty() => Ok(0),
+        Some("") => Ok(0),
         Some(h) if h.parse::<usize>().is_ok() => Ok(h.parse::<usize>().unwrap()),
         _ => Err(Cli(format!("Header {} not valid in GitLab response", header))),
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_option_map_or_else, clippy::useless_conversion)]
 pub mod tests {
     use super::*;
     use crate::api_client::DEFAULT_ENVIRONMENT;
@@ -180,6 +533,10 @@ pub mod tests {
             value: gen_alpha_char(5),
             environment_scope: gen_char(b"ABC*"),
             variable_type: var_type.map_or_else(|| if gen_bool() { GitLabVariableType::EnvVar } else { GitLabVariableType::File }, |t| t),
+            protected: gen_bool(),
+            masked: gen_bool(),
+            raw: gen_bool(),
+            description: None,
         }
     }
 
@@ -192,7 +549,9 @@ pub mod tests {
 
     pub fn httpmock_group_variable() -> impl FnOnce(When, Then) {
         move |when, then| {
-            when.method("GET").path(format!("/api/v4/groups/{}/variables/{}", GEN_GROUP_NAME.clone(), GEN_NAME.clone()));
+            when.method("GET")
+                .path(format!("/api/v4/groups/{}/variables/{}", GEN_GROUP_NAME.clone(), GEN_NAME.clone()))
+                .query_param("filter[environment_scope]", GEN_ENVIRONMENT.as_str());
             then.status(200).header("Content-Type", "application/json").json_body_obj(&GEN_GITLAB_VARIABLE.clone());
         }
     }
@@ -209,6 +568,96 @@ pub mod tests {
         }
     }
 
+    pub fn httpmock_list_group_variables(group: String, variables: Vec<GitLabVariable>) -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}/variables", group));
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .header("x-total", &variables.len().to_string())
+                .json_body_obj(&variables);
+        }
+    }
+
+    pub fn httpmock_instance_variable() -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/admin/ci/variables/{}", GEN_NAME.clone()));
+            then.status(200).header("Content-Type", "application/json").json_body_obj(&GEN_GITLAB_VARIABLE_ALL.clone());
+        }
+    }
+
+    pub fn httpmock_list_instance_variables(variables: Vec<GitLabVariable>) -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path("/api/v4/admin/ci/variables");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .header("x-total", &variables.len().to_string())
+                .json_body_obj(&variables);
+        }
+    }
+
+    pub fn httpmock_project_namespace(kind: &str, full_path: &str) -> impl FnOnce(When, Then) {
+        let namespace = GitLabNamespace {
+            kind: kind.to_owned(),
+            full_path: full_path.to_owned(),
+        };
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/projects/{}", GEN_GITLAB_PROJECT.name.clone()));
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .json_body_obj(&GitLabProjectResponse { namespace });
+        }
+    }
+
+    pub fn httpmock_group(group: GitLabGroup) -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}", encode(&group.full_path)));
+            then.status(200).header("Content-Type", "application/json").json_body_obj(&group);
+        }
+    }
+
+    pub fn httpmock_list_subgroups(group: u64, subgroups: Vec<GitLabGroup>) -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}/subgroups", group));
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .header("x-total", &subgroups.len().to_string())
+                .json_body_obj(&subgroups);
+        }
+    }
+
+    pub fn httpmock_list_group_projects(group: u64, projects: Vec<GitLabProjectSummary>) -> impl FnOnce(When, Then) {
+        move |when, then| {
+            when.method("GET").path(format!("/api/v4/groups/{}/projects", group)).query_param("with_shared", "false");
+            then.status(200)
+                .header("Content-Type", "application/json")
+                .header("x-total", &projects.len().to_string())
+                .json_body_obj(&projects);
+        }
+    }
+
+    pub fn gen_variable_attributes(variable: &GitLabVariable) -> GitLabVariableAttributes {
+        GitLabVariableAttributes {
+            key: variable.key.clone(),
+            value: variable.value.clone(),
+            environment_scope: variable.environment_scope.clone(),
+            variable_type: Some(variable.variable_type),
+            protected: Some(true),
+            masked: None,
+            raw: None,
+            description: None,
+        }
+    }
+
+    pub fn httpmock_write_variable(method: &str, path: String, variable: GitLabVariable) -> impl FnOnce(When, Then) {
+        let (method, attributes) = (method.to_owned(), gen_variable_attributes(&variable).clone_for_request());
+        move |when, then| {
+            when.method(method.as_str()).path(path).json_body_obj(&attributes);
+            then.status(if method == "POST" { 201 } else { 200 })
+                .header("Content-Type", "application/json")
+                .json_body_obj(&variable);
+        }
+    }
+
     pub fn httpmock_list_variables(total: usize, per_page: usize) -> impl FnOnce(When, Then) {
         move |when, then| {
             when.method("GET").path(format!("/api/v4/projects/{}/variables", GEN_GITLAB_PROJECT.name.clone()));
@@ -240,13 +689,223 @@ pub mod tests {
         mock.assert();
     }
 
+    #[test]
+    fn test_should_authenticate_with_detected_mode() {
+        let (token, job_token, oauth_token) = (gen_alpha_char(12), gen_alpha_char(12), gen_alpha_char(12));
+        let detect = |token: &str| AuthMode::detect(token, Some(&job_token), Some(&oauth_token));
+        assert_eq!(detect(&token).header(&token), ("PRIVATE-TOKEN", token.clone()));
+        assert_eq!(detect(&job_token).header(&job_token), ("JOB-TOKEN", job_token.clone()));
+        assert_eq!(detect(&oauth_token).header(&oauth_token), ("Authorization", format!("Bearer {}", oauth_token)));
+        assert_eq!(AuthMode::detect("", Some(""), None), AuthMode::PrivateToken);
+        assert_eq!(AuthMode::detect(&job_token, None, None), AuthMode::PrivateToken);
+    }
+
     #[test]
     fn test_should_get_a_variable_from_group() {
         let server = MockServer::start();
         let mock = server.mock(httpmock_group_variable());
         GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
-            .get_from_group(&GEN_GROUP_NAME, &GEN_NAME)
+            .get_from_group(&GEN_GROUP_NAME, &GEN_NAME, &GEN_ENVIRONMENT)
             .map_or_else(|_| panic!(), |v| assert_eq!(v, *GEN_GITLAB_VARIABLE));
         mock.assert();
     }
+
+    #[test]
+    fn test_should_get_variable_list_from_group() {
+        let variables = gen_variable_list(gen_usize_from_range(1, 50));
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_list_group_variables(GEN_GROUP_NAME.clone(), variables.clone()));
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .list_from_group(&GEN_GROUP_NAME, 1, variables.len())
+            .map_or_else(|_| panic!(), |(l, total)| assert_eq!((l.len(), total), (variables.len(), variables.len())));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_a_variable_from_instance() {
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_instance_variable());
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .get_from_instance(&GEN_NAME)
+            .map_or_else(|_| panic!(), |v| assert_eq!(v, *GEN_GITLAB_VARIABLE_ALL));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_variable_list_from_instance() {
+        let variables = gen_variable_list(gen_usize_from_range(1, 50));
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_list_instance_variables(variables.clone()));
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .list_from_instance(1, variables.len())
+            .map_or_else(|_| panic!(), |(l, total)| assert_eq!((l.len(), total), (variables.len(), variables.len())));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_get_project_namespace() {
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_project_namespace("group", "a-group/a-subgroup"));
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .get_project_namespace(&GEN_GITLAB_PROJECT.name)
+            .map_or_else(|_| panic!(), |n| assert_eq!(n.group_hierarchy(), vec!["a-group/a-subgroup", "a-group"]));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_list_subgroups_and_projects_of_group() {
+        let subgroups = vec![GitLabGroup {
+            id: 2,
+            full_path: "a-group/a-subgroup".to_owned(),
+        }];
+        let projects = vec![GitLabProjectSummary {
+            id: 3,
+            path_with_namespace: "a-group/a-project".to_owned(),
+        }];
+        let server = MockServer::start();
+        let (subgroups_mock, projects_mock) = (
+            server.mock(httpmock_list_subgroups(1, subgroups.clone())),
+            server.mock(httpmock_list_group_projects(1, projects.clone())),
+        );
+        let api = GitLabApiV4::new(server.base_url(), gen_alpha_char(5));
+        assert_eq!(api.list_subgroups("1", 1, 100), Ok((subgroups, 1)));
+        assert_eq!(api.list_group_projects("1", 1, 100), Ok((projects, 1)));
+        subgroups_mock.assert();
+        projects_mock.assert();
+    }
+
+    #[test]
+    fn test_user_namespace_has_no_group_hierarchy() {
+        let namespace = GitLabNamespace {
+            kind: "user".to_owned(),
+            full_path: gen_alpha_char(5),
+        };
+        assert!(namespace.group_hierarchy().is_empty());
+    }
+
+    #[test]
+    fn test_variable_without_environment_scope() {
+        let variable = serde_json::from_str::<GitLabVariable>(r#"{"variable_type":"env_var","key":"A","value":"B"}"#).unwrap();
+        assert_eq!(variable.clone_from_response().environment_scope, DEFAULT_ENVIRONMENT);
+    }
+
+    #[test]
+    fn test_should_create_a_variable_in_project() {
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_write_variable(
+            "POST",
+            format!("/api/v4/projects/{}/variables", *GEN_PROJECT_NAME),
+            GEN_GITLAB_VARIABLE.clone(),
+        ));
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .create_in_project(&GEN_PROJECT_NAME, &gen_variable_attributes(&GEN_GITLAB_VARIABLE))
+            .map_or_else(|_| panic!(), |v| assert_eq!(v, *GEN_GITLAB_VARIABLE));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_update_a_variable_in_project_with_special_scope() {
+        let variable = GitLabVariable {
+            environment_scope: "review/a&b #c+d".to_owned(),
+            ..GEN_GITLAB_VARIABLE.clone()
+        };
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("PUT")
+                .path(format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, variable.key))
+                .query_param("filter[environment_scope]", "review/a&b #c+d");
+            then.status(200).header("Content-Type", "application/json").json_body_obj(&variable);
+        });
+        assert_eq!(
+            GitLabApiV4::new(server.base_url(), gen_alpha_char(5)).update_in_project(&GEN_PROJECT_NAME, &gen_variable_attributes(&variable)),
+            Ok(variable)
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_update_a_variable_in_group() {
+        let server = MockServer::start();
+        let mock = server.mock(httpmock_write_variable(
+            "PUT",
+            format!("/api/v4/groups/{}/variables/{}", *GEN_GROUP_NAME, GEN_GITLAB_VARIABLE_ALL.key),
+            GEN_GITLAB_VARIABLE_ALL.clone(),
+        ));
+        GitLabApiV4::new(server.base_url(), gen_alpha_char(5))
+            .update_in_group(&GEN_GROUP_NAME, &gen_variable_attributes(&GEN_GITLAB_VARIABLE_ALL))
+            .map_or_else(|_| panic!(), |v| assert_eq!(v, *GEN_GITLAB_VARIABLE_ALL));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_delete_a_variable_from_project() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("DELETE")
+                .path(format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, *GEN_NAME))
+                .query_param("filter[environment_scope]", "review/*");
+            then.status(204);
+        });
+        assert_eq!(
+            GitLabApiV4::new(server.base_url(), gen_alpha_char(5)).delete_from_project(&GEN_PROJECT_NAME, &GEN_NAME, "review/*"),
+            Ok(())
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_delete_a_variable_from_group() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("DELETE")
+                .path(format!("/api/v4/groups/{}/variables/{}", *GEN_GROUP_NAME, *GEN_NAME))
+                .query_param("filter[environment_scope]", "*");
+            then.status(204);
+        });
+        assert_eq!(
+            GitLabApiV4::new(server.base_url(), gen_alpha_char(5)).delete_from_group(&GEN_GROUP_NAME, &GEN_NAME, DEFAULT_ENVIRONMENT),
+            Ok(())
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn test_variable_details() {
+        let variable = GitLabVariable {
+            protected: false,
+            masked: false,
+            raw: false,
+            description: None,
+            ..gen_variable(None)
+        };
+        assert_eq!(variable.details(), "");
+        let variable = GitLabVariable {
+            protected: true,
+            raw: true,
+            description: Some("Database password".to_owned()),
+            ..variable
+        };
+        assert_eq!(variable.details(), " (protected, raw; Database password)");
+    }
+
+    #[test]
+    fn test_should_deserialize_variable_flags() {
+        let variable = serde_json::from_str::<GitLabVariable>(
+            r#"{"variable_type":"env_var","key":"A","value":"a","protected":true,"masked":true,"raw":false,"environment_scope":"*","description":"A variable"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            (variable.protected, variable.masked, variable.raw, variable.description),
+            (true, true, false, Some("A variable".to_owned()))
+        );
+        let variable = serde_json::from_str::<GitLabVariable>(r#"{"variable_type":"file","key":"A","value":"a"}"#).unwrap();
+        assert_eq!((variable.protected, variable.masked, variable.raw, variable.description), (false, false, false, None));
+    }
+
+    #[test]
+    fn test_variable_attributes_use_api_environment_scope() {
+        let attributes = gen_variable_attributes(&GEN_GITLAB_VARIABLE_ALL).clone_for_request();
+        assert_eq!(attributes.environment_scope, "*");
+        assert_eq!(serde_json::to_value(&attributes).unwrap().get("masked"), None);
+    }
 }
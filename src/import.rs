@@ -0,0 +1,522 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::api_client;
+use crate::app_error::{AppError, AppError::InvalidInput, Result};
+use crate::config::Profile;
+use crate::delete_variable::source_description;
+use crate::dotenv::{all_from_api, RequestConfig};
+use crate::gitlab_api::{GitLabVariable, GitLabVariableAttributes, GitLabVariableType, VariableSource};
+use crate::io::IO;
+use crate::set_variable::{bool_param_from_args, source_from_args, write_variable};
+use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::path::Path;
+
+/// Arguments for `import` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportCommand {
+    /// Dotenv file to import
+    file: String,
+    /// Project or group where variables will be imported
+    source: VariableSource,
+    /// Environment scope of imported variables
+    environment: String,
+    /// Only print what would change
+    dry_run: bool,
+    /// Whether imported variables are protected
+    protected: Option<bool>,
+    /// Whether imported variables are masked
+    masked: Option<bool>,
+    /// Whether imported variables are raw
+    raw: Option<bool>,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+/// Change to apply to a variable in GitLab
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportAction {
+    /// Variable does not exist in the environment scope
+    Create,
+    /// Variable exists in the environment scope with a different value, type or flags
+    Update,
+    /// Variable exists in the environment scope with the same value, type and flags
+    Unchanged,
+}
+
+impl Display for ImportAction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            ImportAction::Create => write!(f, "create"),
+            ImportAction::Update => write!(f, "update"),
+            ImportAction::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+impl Performable for ImportCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            app_info!("Reading variables from {}...", self.file);
+            let variables = read_dotenv_file(&self.file)?;
+            app_info!("Getting variables from {}...", source_description(&self.source));
+            let remote = all_from_api(RequestConfig::new(&self.url, &self.token, self.source.clone(), 1, 100), num_cpus::get())?;
+            Ok((self.clone(), plan_import(&self, variables, &remote)))
+        })
+        .map(|res: Result<(ImportCommand, Vec<(ImportAction, GitLabVariableAttributes)>)>| {
+            res.and_then(|(cmd, plan)| match cmd.dry_run {
+                true => Ok(plan.iter().for_each(|(action, v)| println!("{}\t{}\t{}", action, v.key, v.environment_scope))),
+                _ => {
+                    let api = api_client(&cmd.url, &cmd.token);
+                    plan.iter()
+                        .filter(|(action, _)| *action != ImportAction::Unchanged)
+                        .try_for_each(|(action, v)| write_variable(&api, &cmd.source, v, *action == ImportAction::Update).map(|_| app_info!("Variable {} {}d", v.key, action)))?;
+                    Ok(app_success!("Variables imported successfully in scope {}", cmd.environment))
+                }
+            })
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for ImportCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        ImportCommand {
+            file: argm.value_of("FILE").unwrap().to_owned(),
+            source: source_from_args(argm, &profile),
+            environment: argm.value_of("environment").unwrap().to_owned(),
+            dry_run: argm.is_present("dry-run"),
+            protected: bool_param_from_args(argm, "protected"),
+            masked: bool_param_from_args(argm, "masked"),
+            raw: bool_param_from_args(argm, "raw"),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Returns the list of variables defined in a dotenv file. Variables whose value is the path of an existing
+/// `<VARIABLE_NAME>.var` file (as written by `dotenv` command) are read as variables of type "File". Relative
+/// paths are resolved from the folder of the dotenv file.
+///
+/// # Arguments
+///
+/// * `file` - Path of dotenv file
+///
+pub(crate) fn read_dotenv_file(file: &str) -> Result<Vec<(String, String, GitLabVariableType)>> {
+    let content = fs::read_to_string(file).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", file, e)))?;
+    let folder = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    parse_dotenv(&content)?
+        .into_iter()
+        .map(|(key, value)| match value.ends_with(&format!("{}.var", key)) && folder.join(&value).is_file() {
+            true => Ok((key, fs::read_to_string(folder.join(&value))?, GitLabVariableType::File)),
+            _ => Ok((key, value, GitLabVariableType::EnvVar)),
+        })
+        .collect()
+}
+
+/// Returns the change to apply for each local variable, according to variables defined in GitLab
+///
+/// # Arguments
+///
+/// * `cmd`       - Import command
+/// * `variables` - Local variables (key, value and type)
+/// * `remote`    - Variables defined in GitLab
+///
+fn plan_import(cmd: &ImportCommand, variables: Vec<(String, String, GitLabVariableType)>, remote: &[GitLabVariable]) -> Vec<(ImportAction, GitLabVariableAttributes)> {
+    variables
+        .into_iter()
+        .map(|(key, value, variable_type)| {
+            let action = match remote.iter().find(|r| r.key == key && r.environment_scope == cmd.environment) {
+                None => ImportAction::Create,
+                Some(r)
+                    if r.value == value
+                        && r.variable_type == variable_type
+                        && cmd.protected.unwrap_or(r.protected) == r.protected
+                        && cmd.masked.unwrap_or(r.masked) == r.masked
+                        && cmd.raw.unwrap_or(r.raw) == r.raw =>
+                {
+                    ImportAction::Unchanged
+                }
+                _ => ImportAction::Update,
+            };
+            let attributes = GitLabVariableAttributes {
+                key,
+                value,
+                environment_scope: cmd.environment.clone(),
+                variable_type: Some(variable_type),
+                protected: cmd.protected,
+                masked: cmd.masked,
+                raw: cmd.raw,
+                description: None,
+            };
+            (action, attributes)
+        })
+        .collect()
+}
+
+/// Parses the content of a dotenv file and returns its variables in order. Supported statements are POSIX
+/// `export KEY="value"`, fish `set -gx KEY "value"` and plain `KEY=value`. Empty lines and comments are ignored,
+/// including comments following a value after a blank (`KEY=value # comment`). As when the file is sourced, the
+/// last statement of a key wins.
+///
+/// # Arguments
+///
+/// * `content` - Content of dotenv file
+///
+pub(crate) fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>> {
+    let mut parser = DotEnvParser {
+        chars: content.chars().collect(),
+        pos: 0,
+    };
+    let mut variables = vec![];
+    while let Some(statement) = parser.next_statement()? {
+        variables.retain(|(key, _): &(String, String)| *key != statement.0);
+        variables.push(statement);
+    }
+    Ok(variables)
+}
+
+/// Character stream parser for dotenv statements
+struct DotEnvParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl DotEnvParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.pos += 1;
+        self.chars.get(self.pos - 1).copied()
+    }
+
+    fn line(&self) -> usize {
+        self.chars[..self.pos.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1
+    }
+
+    fn error(&self, message: &str) -> AppError {
+        InvalidInput(format!("Line {}: {}", self.line(), message))
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads characters until a blank, a new line or `=`
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| !c.is_whitespace() && *c != '=') {
+            word.push(c);
+            self.pos += 1;
+        }
+        word
+    }
+
+    fn key(&mut self) -> Result<String> {
+        let key = self.word();
+        let mut chars = key.chars();
+        match chars.next() {
+            Some(c) if (c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') => Ok(key),
+            _ => Err(self.error(&format!("\"{}\" is not a valid variable name", key))),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected \"{}\"", expected))),
+        }
+    }
+
+    fn next_statement(&mut self) -> Result<Option<(String, String)>> {
+        loop {
+            match self.peek() {
+                None => return Ok(None),
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('#') => while !matches!(self.next(), Some('\n') | None) {},
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        let word = self.word();
+        match (word.as_str(), self.peek()) {
+            ("export", Some(' ')) | ("export", Some('\t')) => {
+                self.skip_blanks();
+                let key = self.key()?;
+                self.expect('=')?;
+                Ok(Some((key, self.value(false)?)))
+            }
+            ("set", Some(' ')) | ("set", Some('\t')) => {
+                self.skip_blanks();
+                if !self.word().starts_with('-') {
+                    return Err(self.error("expected options of fish \"set\" command"));
+                }
+                self.skip_blanks();
+                let key = self.key()?;
+                self.skip_blanks();
+                Ok(Some((key, self.value(true)?)))
+            }
+            _ => {
+                self.pos = start;
+                let key = self.key()?;
+                self.expect('=')?;
+                Ok(Some((key, self.value(false)?)))
+            }
+        }
+    }
+
+    /// Reads a value until an unquoted new line or comment, removing quotes and escape characters
+    fn value(&mut self, fish: bool) -> Result<String> {
+        let (mut value, mut blanks) = (String::new(), String::new());
+        loop {
+            match self.next() {
+                None | Some('\n') => return Ok(value),
+                Some(c) if c == ' ' || c == '\t' || c == '\r' => blanks.push(c),
+                Some('#') if !blanks.is_empty() => {
+                    while !matches!(self.next(), Some('\n') | None) {}
+                    return Ok(value);
+                }
+                Some(c) => {
+                    value.push_str(&blanks);
+                    blanks.clear();
+                    match c {
+                        '\'' => self.single_quoted(&mut value, fish)?,
+                        '"' => self.double_quoted(&mut value)?,
+                        '\\' => match self.next() {
+                            Some('n') if fish => value.push('\n'),
+                            Some('t') if fish => value.push('\t'),
+                            Some('\n') => (),
+                            Some(e) => value.push(e),
+                            None => return Err(self.error("unexpected end of file after \"\\\"")),
+                        },
+                        _ => value.push(c),
+                    }
+                }
+            }
+        }
+    }
+
+    fn single_quoted(&mut self, value: &mut String, fish: bool) -> Result<()> {
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated single quote")),
+                Some('\'') => return Ok(()),
+                Some('\\') if fish && matches!(self.peek(), Some('\'') | Some('\\')) => value.push(self.next().unwrap()),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self, value: &mut String) -> Result<()> {
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated double quote")),
+                Some('"') => return Ok(()),
+                Some('\\') => match self.next() {
+                    Some(e) if matches!(e, '"' | '\\' | '$' | '`') => value.push(e),
+                    Some('\n') => (),
+                    Some(e) => {
+                        value.push('\\');
+                        value.push(e);
+                    }
+                    None => return Err(self.error("unterminated double quote")),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gen::tests::gen_alpha_char;
+    use crate::gitlab_api::tests::*;
+    use crate::shell_types::tests::gen_shell_value;
+    use crate::shell_types::ShellType;
+
+    fn gen_import_command(dry_run: bool) -> ImportCommand {
+        ImportCommand {
+            file: ".env".to_owned(),
+            source: VariableSource::Project(GEN_PROJECT_NAME.clone()),
+            environment: GEN_ENVIRONMENT.clone(),
+            dry_run,
+            protected: None,
+            masked: None,
+            raw: None,
+            url: "gitlab.com".to_owned(),
+            token: GEN_TOKEN.clone(),
+        }
+    }
+
+    #[test]
+    fn test_should_create_import_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "import",
+                ".env",
+                &format!("-e={}", *GEN_ENVIRONMENT),
+                &format!("-p={}", *GEN_PROJECT_NAME),
+                "--dry-run",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("import")
+            .map(|a| assert_eq!(ImportCommand::from(a), gen_import_command(true)))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_parse_dotenv_formats() {
+        let content = "# A comment\nexport A=\"one\"\n\nset -gx B \"two\"\nC=three\n  D='four five'  \nexport E=\n";
+        assert_eq!(
+            parse_dotenv(content),
+            Ok(vec![
+                ("A".to_owned(), "one".to_owned()),
+                ("B".to_owned(), "two".to_owned()),
+                ("C".to_owned(), "three".to_owned()),
+                ("D".to_owned(), "four five".to_owned()),
+                ("E".to_owned(), "".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_dotenv_duplicated_keys() {
+        assert_eq!(
+            parse_dotenv("export A=one\nB=two\nset -gx A three\n"),
+            Ok(vec![("B".to_owned(), "two".to_owned()), ("A".to_owned(), "three".to_owned())])
+        );
+    }
+
+    #[test]
+    fn test_parse_dotenv_quoting() {
+        let content = "export A=\"a \\\"quoted\\\" \\$value\"\nexport B='it'\\''s'\nset -gx C 'it\\'s'\nexport D=\"multi\nline\"\nE=plain value  \n";
+        assert_eq!(
+            parse_dotenv(content),
+            Ok(vec![
+                ("A".to_owned(), "a \"quoted\" $value".to_owned()),
+                ("B".to_owned(), "it's".to_owned()),
+                ("C".to_owned(), "it's".to_owned()),
+                ("D".to_owned(), "multi\nline".to_owned()),
+                ("E".to_owned(), "plain value".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_dotenv_inline_comments() {
+        let content = "KEY=v # note\nexport A=1 # c\nset -gx B \"two\" # c\nC=\"quoted # kept\" # c\nD=no#comment\nE= # empty\n";
+        assert_eq!(
+            parse_dotenv(content),
+            Ok(vec![
+                ("KEY".to_owned(), "v".to_owned()),
+                ("A".to_owned(), "1".to_owned()),
+                ("B".to_owned(), "two".to_owned()),
+                ("C".to_owned(), "quoted # kept".to_owned()),
+                ("D".to_owned(), "no#comment".to_owned()),
+                ("E".to_owned(), "".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_read_dotenv_file_resolves_var_files_from_its_folder() {
+        let folder = env::temp_dir().join(format!("import-{}", gen_alpha_char(8)));
+        fs::create_dir_all(folder.join(".env.dev")).unwrap();
+        fs::write(folder.join(".env.dev").join("A_FILE.var"), "file content").unwrap();
+        fs::write(folder.join(".env"), "export A_FILE=\".env.dev/A_FILE.var\"\nexport B=\".env.dev/OTHER.var\"\n").unwrap();
+        let variables = read_dotenv_file(folder.join(".env").to_str().unwrap());
+        fs::remove_dir_all(&folder).ok();
+        assert_eq!(
+            variables,
+            Ok(vec![
+                ("A_FILE".to_owned(), "file content".to_owned(), GitLabVariableType::File),
+                ("B".to_owned(), ".env.dev/OTHER.var".to_owned(), GitLabVariableType::EnvVar),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_dotenv_generated_commands() {
+        let key = format!("KEY_{}", gen_variable(None).key);
+        (0..50).map(|_| gen_shell_value()).for_each(|value| {
+            [ShellType::Posix, ShellType::Fish]
+                .iter()
+                .for_each(|shell| assert_eq!(parse_dotenv(&shell.export_command(key.clone(), value.clone())), Ok(vec![(key.clone(), value.clone())])))
+        });
+    }
+
+    #[test]
+    fn test_parse_dotenv_errors() {
+        assert!(matches!(parse_dotenv("A=1\nexport B=\"unterminated\n"), Err(InvalidInput(e)) if e.starts_with("Line 3")));
+        assert!(matches!(parse_dotenv("1A=1\n"), Err(InvalidInput(e)) if e.starts_with("Line 1")));
+        assert!(parse_dotenv("export A\n").is_err());
+    }
+
+    #[test]
+    fn test_plan_import() {
+        let (existing, changed) = (gen_variable(Some(GitLabVariableType::EnvVar)), gen_variable(Some(GitLabVariableType::EnvVar)));
+        let remote = vec![
+            GitLabVariable {
+                environment_scope: GEN_ENVIRONMENT.clone(),
+                ..existing.clone()
+            },
+            GitLabVariable {
+                environment_scope: GEN_ENVIRONMENT.clone(),
+                ..changed.clone()
+            },
+        ];
+        let local = vec![
+            (existing.key.clone(), existing.value.clone(), GitLabVariableType::EnvVar),
+            (changed.key.clone(), "new-value".to_owned(), GitLabVariableType::EnvVar),
+            ("NEW_VARIABLE".to_owned(), "a-value".to_owned(), GitLabVariableType::EnvVar),
+        ];
+        assert_eq!(
+            plan_import(&gen_import_command(true), local, &remote)
+                .into_iter()
+                .map(|(a, v)| (a, v.key))
+                .collect::<Vec<(ImportAction, String)>>(),
+            vec![
+                (ImportAction::Unchanged, existing.key),
+                (ImportAction::Update, changed.key),
+                (ImportAction::Create, "NEW_VARIABLE".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_import_updates_variables_with_different_flags() {
+        let existing = GitLabVariable {
+            environment_scope: GEN_ENVIRONMENT.clone(),
+            protected: false,
+            ..gen_variable(Some(GitLabVariableType::EnvVar))
+        };
+        let local = vec![(existing.key.clone(), existing.value.clone(), GitLabVariableType::EnvVar)];
+        let plan = |protected| {
+            let cmd = ImportCommand {
+                protected,
+                ..gen_import_command(true)
+            };
+            plan_import(&cmd, local.clone(), std::slice::from_ref(&existing))
+                .into_iter()
+                .map(|(a, _)| a)
+                .collect::<Vec<ImportAction>>()
+        };
+        assert_eq!(plan(Some(false)), vec![ImportAction::Unchanged]);
+        assert_eq!(plan(Some(true)), vec![ImportAction::Update]);
+    }
+}
@@ -1,17 +1,33 @@
-//! `gitlab-rescue` is a CLI tool for getting and importing GitLab CI/CD variables from a project (Read only)
+//! `gitlab-rescue` is a CLI tool for getting and importing GitLab CI/CD variables from a project
 //!
 
 mod macros;
 
 pub mod api_client;
 pub mod app_error;
+pub mod backup;
 pub mod clap_app;
+pub mod compare;
+pub mod config;
+pub mod copy;
+pub mod delete_variable;
+pub mod diff;
 pub mod dotenv;
+pub mod exec;
+pub mod expand;
 pub mod gen;
 pub mod get_variable;
 mod gitlab_api;
+pub mod import;
 pub mod io;
+pub mod output_formats;
+pub mod repository;
+pub mod restore;
+pub mod scope;
+pub mod set_variable;
 pub mod shell_types;
+pub mod sync;
+pub mod token;
 
 use crate::app_error::Result;
 use crate::io::IO;
@@ -23,29 +23,35 @@ macro_rules! app_warning {
 }
 
 #[macro_export]
-/// Extract GITLAB_URL from clap args
+/// Extract GITLAB_URL from clap args, GITLAB_URL variable or the defaults of the repository and the selected profile
 macro_rules! extract_url {
-    ($clap_args:expr) => {
+    ($clap_args:expr, $profile:expr) => {
         $clap_args
             .value_of("url")
-            .map_or_else(|| env::var("GITLAB_URL").unwrap_or(String::from("https://gitlab.com")), |s| s.to_owned())
+            .map(|s| s.to_owned())
+            .or_else(|| env::var("GITLAB_URL").ok())
+            .or_else(|| $profile.url.clone())
+            .unwrap_or_else(|| $crate::api_client::DEFAULT_URL.to_owned())
     };
 }
 
 #[macro_export]
-/// Extract GITLAB_API_TOKEN from clap args
+/// Extract GitLab API token with [resolve_token](fn@crate::token::resolve_token). Errors are reported by
+/// [check_token](fn@crate::token::check_token) before commands are run.
 macro_rules! extract_token {
-    ($clap_args:expr) => {
-        $clap_args
-            .value_of("token")
-            .map_or_else(|| env::var("GITLAB_API_TOKEN").unwrap_or(String::new()), |s| s.to_owned())
+    ($clap_args:expr, $profile:expr) => {
+        $crate::token::resolve_token($clap_args, &$profile).unwrap_or_default()
     };
 }
 
 #[macro_export]
-/// Extract GitLab environment from clap args
+/// Extract GitLab environment from clap args or the defaults of the repository and the selected profile
 macro_rules! extract_environment {
-    ($clap_args:expr) => {
-        $clap_args.value_of("environment").map_or_else(|| "All".to_owned(), |v| v.to_owned())
+    ($clap_args:expr, $profile:expr) => {
+        $clap_args
+            .value_of("environment")
+            .map(|v| v.to_owned())
+            .or_else(|| $profile.environment.clone())
+            .unwrap_or_else(|| $crate::api_client::DEFAULT_ENVIRONMENT.to_owned())
     };
 }
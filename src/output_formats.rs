@@ -0,0 +1,337 @@
+use crate::gitlab_api::GitLabVariableType;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Output formats of `dotenv` command
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Commands for exporting variables in a shell (see [ShellType](enum@crate::shell_types::ShellType))
+    Shell,
+    /// Plain `KEY=VALUE` lines, as read by docker-compose and python-dotenv
+    Dotenv,
+    /// JSON array of variables
+    Json,
+    /// YAML list of variables
+    Yaml,
+    /// Env-file of `docker run --env-file`: unquoted `KEY=VALUE` lines
+    Docker,
+    /// Kubernetes `v1/Secret` manifest
+    Secret,
+    /// Kubernetes `v1/ConfigMap` manifest
+    ConfigMap,
+    /// File of systemd `EnvironmentFile=` directive
+    Systemd,
+    /// `.envrc` file of direnv that loads variables with `gitlab-rescue` each time direnv loads it
+    Direnv,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            OutputFormat::Shell => write!(f, "shell"),
+            OutputFormat::Dotenv => write!(f, "dotenv"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Yaml => write!(f, "yaml"),
+            OutputFormat::Docker => write!(f, "docker"),
+            OutputFormat::Secret => write!(f, "secret"),
+            OutputFormat::ConfigMap => write!(f, "configmap"),
+            OutputFormat::Systemd => write!(f, "systemd"),
+            OutputFormat::Direnv => write!(f, "direnv"),
+        }
+    }
+}
+
+impl From<&str> for OutputFormat {
+    /// Returns the output format of a name as accepted by `--format` option. Unknown names are shell commands.
+    fn from(name: &str) -> Self {
+        match name {
+            "dotenv" => OutputFormat::Dotenv,
+            "json" => OutputFormat::Json,
+            "yaml" => OutputFormat::Yaml,
+            "docker" => OutputFormat::Docker,
+            "secret" => OutputFormat::Secret,
+            "configmap" => OutputFormat::ConfigMap,
+            "systemd" => OutputFormat::Systemd,
+            "direnv" => OutputFormat::Direnv,
+            _ => OutputFormat::Shell,
+        }
+    }
+}
+
+/// Variable written by structured output formats (JSON and YAML)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExportedVariable {
+    /// Name of the variable
+    pub key: String,
+    /// Value of the variable. For variables of type "File", the content of the file.
+    pub value: String,
+    /// Environment scope the value was taken from
+    pub environment_scope: String,
+    /// Type of the variable: env_var or file
+    pub variable_type: GitLabVariableType,
+    /// Whether the variable is only exported in protected branches and tags
+    pub protected: bool,
+    /// Whether the variable is masked in job logs
+    pub masked: bool,
+    /// Whether the variable is treated as a raw string (no variable expansion)
+    pub raw: bool,
+    /// Description of the variable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Path of the file where a variable of type "File" is stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Metadata of a Kubernetes manifest
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct KubernetesMetadata {
+    /// Name of the resource
+    pub name: String,
+    /// Namespace of the resource. If missing, the namespace of the current context is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// Labels of the resource
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Kubernetes manifest of a `v1/Secret` or a `v1/ConfigMap`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesManifest {
+    /// API version of the resource
+    pub api_version: String,
+    /// Kind of the resource: Secret or ConfigMap
+    pub kind: String,
+    /// Metadata of the resource
+    pub metadata: KubernetesMetadata,
+    /// Type of a Secret
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub secret_type: Option<String>,
+    /// Entries of the resource. Values of Secrets are base64-encoded.
+    pub data: BTreeMap<String, String>,
+}
+
+impl KubernetesManifest {
+    /// Returns an `Opaque` Secret with `entries` base64-encoded
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the Secret
+    /// * `entries`  - Keys and plain values of the Secret
+    ///
+    pub fn secret(metadata: KubernetesMetadata, entries: BTreeMap<String, String>) -> Self {
+        KubernetesManifest {
+            api_version: "v1".to_owned(),
+            kind: "Secret".to_owned(),
+            metadata,
+            secret_type: Some("Opaque".to_owned()),
+            data: entries.into_iter().map(|(k, v)| (k, base64::encode(v))).collect(),
+        }
+    }
+
+    /// Returns a ConfigMap with `entries`
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the ConfigMap
+    /// * `entries`  - Keys and values of the ConfigMap
+    ///
+    pub fn config_map(metadata: KubernetesMetadata, entries: BTreeMap<String, String>) -> Self {
+        KubernetesManifest {
+            api_version: "v1".to_owned(),
+            kind: "ConfigMap".to_owned(),
+            metadata,
+            secret_type: None,
+            data: entries,
+        }
+    }
+}
+
+/// Returns a valid name for a Kubernetes resource (lowercase alphanumeric characters and `-`)
+///
+/// # Arguments
+///
+/// * `name` - Name to convert
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::output_formats::kubernetes_name;
+///
+/// assert_eq!(kubernetes_name("My_Project-review/feature"), "my-project-review-feature");
+/// ```
+///
+pub fn kubernetes_name(name: &str) -> String {
+    let name = name.to_lowercase().chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect::<String>();
+    name.trim_matches('-').to_owned()
+}
+
+/// Returns a plain `KEY=VALUE` line, or `None` if docker-compose and python-dotenv can not both read the value back
+/// unchanged. Values without single quotes or backslashes are single-quoted, so both read them literally. Other
+/// values are double-quoted, escaping backslashes, double quotes and new lines, but only if they do not contain `$`
+/// (python-dotenv keeps `\$` while docker-compose expands `$`) and do not end with `"` or `\` (docker-compose
+/// takes the closing quote as escaped or trims it).
+///
+/// # Arguments
+///
+/// * `variable` - Name of the variable
+/// * `value`    - Value of the variable
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::output_formats::dotenv_line;
+///
+/// assert_eq!(dotenv_line("GREETING", "hello $USER"), Some("GREETING='hello $USER'".to_owned()));
+/// assert_eq!(dotenv_line("GREETING", "it's me"), Some("GREETING=\"it's me\"".to_owned()));
+/// assert_eq!(dotenv_line("GREETING", "it's $USER"), None);
+/// ```
+///
+pub fn dotenv_line(variable: &str, value: &str) -> Option<String> {
+    if !value.contains(['\'', '\\']) {
+        return Some(format!("{}='{}'", variable, value));
+    }
+    match value.contains('$') || value.ends_with(['"', '\\']) {
+        true => None,
+        _ => Some(format!(
+            "{}=\"{}\"",
+            variable,
+            value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r")
+        )),
+    }
+}
+
+/// Returns a line of a Docker env-file, or `None` if the value can not be represented. Docker takes everything
+/// after the first `=` as the value, without quotes or escape sequences, so values with new lines are rejected.
+///
+/// # Arguments
+///
+/// * `variable` - Name of the variable
+/// * `value`    - Value of the variable
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::output_formats::docker_env_line;
+///
+/// assert_eq!(docker_env_line("GREETING", "it's \"$USER\""), Some("GREETING=it's \"$USER\"".to_owned()));
+/// assert_eq!(docker_env_line("GREETING", "hello\nworld"), None);
+/// ```
+///
+pub fn docker_env_line(variable: &str, value: &str) -> Option<String> {
+    match value.contains(['\n', '\r']) {
+        true => None,
+        _ => Some(format!("{}={}", variable, value)),
+    }
+}
+
+/// Returns a line of a systemd `EnvironmentFile=`. Values are double-quoted, so whitespace and new lines are
+/// kept, and the characters that systemd unescapes inside double quotes (`"`, `\`, `` ` `` and `$`) are escaped.
+///
+/// # Arguments
+///
+/// * `variable` - Name of the variable
+/// * `value`    - Value of the variable
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::output_formats::systemd_env_line;
+///
+/// assert_eq!(systemd_env_line("GREETING", "say \"hi\" to $USER"), "GREETING=\"say \\\"hi\\\" to \\$USER\"");
+/// ```
+///
+pub fn systemd_env_line(variable: &str, value: &str) -> String {
+    let escaped = value.chars().fold(String::new(), |mut acc, c| {
+        if matches!(c, '"' | '\\' | '`' | '$') {
+            acc.push('\\');
+        }
+        acc.push(c);
+        acc
+    });
+    format!("{}=\"{}\"", variable, escaped)
+}
+
+/// Returns `true` if docker-compose reads the line of [docker_env_line](fn@docker_env_line) with the same value as
+/// Docker. Compose interpolates `$`, removes surrounding quotes and treats ` #` as the start of a comment.
+///
+/// # Arguments
+///
+/// * `value` - Value of the variable
+///
+pub fn is_compose_literal(value: &str) -> bool {
+    !(value.contains('$') || value.contains(" #") || value.starts_with(['\'', '"']))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format_from_name() {
+        assert_eq!(
+            ["shell", "dotenv", "json", "yaml", "docker"]
+                .iter()
+                .map(|n| OutputFormat::from(*n))
+                .collect::<Vec<OutputFormat>>(),
+            vec![OutputFormat::Shell, OutputFormat::Dotenv, OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Docker]
+        );
+    }
+
+    #[test]
+    fn test_dotenv_line() {
+        assert_eq!(dotenv_line("A", "first\nsecond \"$B\""), Some("A='first\nsecond \"$B\"'".to_owned()));
+        assert_eq!(dotenv_line("A", "it's \"a\"\n\\ b"), Some("A=\"it's \\\"a\\\"\\n\\\\ b\"".to_owned()));
+        assert_eq!(dotenv_line("A", "C:\\Users\\me\r\n"), Some("A=\"C:\\\\Users\\\\me\\r\\n\"".to_owned()));
+        assert_eq!(dotenv_line("A", "it's $HOME ${USER}"), None);
+        assert_eq!(dotenv_line("A", "ends with \\"), None);
+        assert_eq!(dotenv_line("A", "it's \"quoted\""), None);
+    }
+
+    #[test]
+    fn test_docker_env_line() {
+        assert_eq!(docker_env_line("A", " 'quoted' # not a comment\\"), Some("A= 'quoted' # not a comment\\".to_owned()));
+        assert_eq!(docker_env_line("A", "first\r\nsecond"), None);
+    }
+
+    #[test]
+    fn test_systemd_env_line() {
+        assert_eq!(systemd_env_line("A", " it's\n`id` \\ "), "A=\" it's\n\\`id\\` \\\\ \"");
+    }
+
+    #[test]
+    fn test_is_compose_literal() {
+        assert!(is_compose_literal("plain value, with 'quotes' and a#hash"));
+        assert!(!is_compose_literal("$HOME"));
+        assert!(!is_compose_literal("value # comment"));
+        assert!(!is_compose_literal("'quoted'"));
+    }
+
+    #[test]
+    fn test_kubernetes_secret_manifest() {
+        let metadata = KubernetesMetadata {
+            name: "app".to_owned(),
+            namespace: Some("production".to_owned()),
+            labels: vec![("team".to_owned(), "backend".to_owned())].into_iter().collect(),
+        };
+        let entries = vec![("GREETING".to_owned(), "hello".to_owned())].into_iter().collect::<BTreeMap<String, String>>();
+        assert_eq!(
+            serde_yaml::to_string(&KubernetesManifest::secret(metadata.clone(), entries.clone())).unwrap(),
+            "---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: app\n  namespace: production\n  labels:\n    team: backend\ntype: Opaque\ndata:\n  GREETING: aGVsbG8=\n"
+        );
+        let config_map = KubernetesManifest::config_map(
+            KubernetesMetadata {
+                labels: BTreeMap::new(),
+                ..metadata
+            },
+            entries,
+        );
+        assert_eq!(
+            serde_yaml::to_string(&config_map).unwrap(),
+            "---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\n  namespace: production\ndata:\n  GREETING: hello\n"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::config::Profile;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the file with defaults of a repository, in the root folder of the repository
+pub const REPOSITORY_CONFIG: &str = ".gitlab-rescue.toml";
+
+/// Defaults of a repository, read from [REPOSITORY_CONFIG](constant@REPOSITORY_CONFIG). They take precedence over
+/// the values discovered from `origin` remote. The URL is only discovered from `origin` remote, since this file is
+/// usually committed and tokens must not be sent to a host chosen by the repository.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryConfig {
+    /// Project of the repository (ID or NAMESPACE/PROJECT_NAME)
+    pub project: Option<String>,
+    /// Default GitLab CI/CD environment
+    pub environment: Option<String>,
+    /// Default shell of `dotenv` command
+    pub shell: Option<String>,
+}
+
+impl From<RepositoryConfig> for Profile {
+    fn from(config: RepositoryConfig) -> Self {
+        Profile {
+            project: config.project,
+            environment: config.environment,
+            shell: config.shell,
+            ..Profile::default()
+        }
+    }
+}
+
+/// Returns the defaults of the git repository that contains `dir`: URL and project discovered from `origin` remote,
+/// the project overridden by [REPOSITORY_CONFIG](constant@REPOSITORY_CONFIG) file. If `dir` is not inside a git repository, an
+/// empty profile is returned.
+///
+/// # Arguments
+///
+/// * `dir` - Folder inside the repository
+///
+pub fn repository_defaults(dir: &Path) -> Result<Profile> {
+    match find_repository_root(dir) {
+        Some(root) => {
+            let discovered = git_config_path(&root)
+                .and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|config| remote_url(&config, "origin"))
+                .and_then(|url| gitlab_project_from_remote(&url))
+                .map_or_else(Profile::default, |(url, project)| Profile {
+                    url: Some(url),
+                    project: Some(project),
+                    ..Profile::default()
+                });
+            let file = root.join(REPOSITORY_CONFIG);
+            match file.exists() {
+                true => Ok(Profile::from(read_repository_config(&file)?).or(discovered)),
+                _ => Ok(discovered),
+            }
+        }
+        None => Ok(Profile::default()),
+    }
+}
+
+/// Reads a [REPOSITORY_CONFIG](constant@REPOSITORY_CONFIG) file
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+///
+pub fn read_repository_config(path: &Path) -> Result<RepositoryConfig> {
+    let content = fs::read_to_string(path).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", path.display(), e)))?;
+    toml::from_str::<RepositoryConfig>(&content).map_err(|e| InvalidInput(format!("File {} is not a valid repository configuration. Error: {}", path.display(), e)))
+}
+
+/// Returns the root folder of the git repository that contains `dir`, i.e. the closest folder with a `.git` entry
+///
+/// # Arguments
+///
+/// * `dir` - Folder inside the repository
+///
+pub fn find_repository_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find(|d| d.join(".git").exists()).map(|d| d.to_path_buf())
+}
+
+/// Returns the path of the git configuration of a repository. In worktrees and submodules `.git` is a file that
+/// points to the git folder, and worktrees share the configuration of their main repository.
+///
+/// # Arguments
+///
+/// * `root` - Root folder of the repository
+///
+fn git_config_path(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    let git_dir = match dot_git.is_dir() {
+        true => dot_git,
+        _ => root.join(fs::read_to_string(&dot_git).ok()?.trim().strip_prefix("gitdir:")?.trim()),
+    };
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => Some(git_dir.join(common_dir.trim()).join("config")),
+        _ => Some(git_dir.join("config")),
+    }
+}
+
+/// Returns the URL of a remote defined in the content of a git configuration file
+///
+/// # Arguments
+///
+/// * `git_config` - Content of the git configuration file
+/// * `remote`     - Name of the remote
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::repository::remote_url;
+///
+/// let config = "[core]\n\tbare = false\n[remote \"origin\"]\n\turl = git@gitlab.com:group/app.git\n";
+/// assert_eq!(remote_url(config, "origin"), Some("git@gitlab.com:group/app.git".to_owned()));
+/// ```
+///
+pub fn remote_url(git_config: &str, remote: &str) -> Option<String> {
+    let section = format!("[remote \"{}\"]", remote);
+    git_config
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "url")
+        .map(|(_, value)| value.trim().trim_matches('"').to_owned())
+}
+
+/// Returns the URL of GitLab and the NAMESPACE/PROJECT_NAME of a git remote. SSH remotes (`git@host:path` and
+/// `ssh://`) are reached through `https://host`, and HTTP(S) remotes through the same scheme, host and port.
+/// Other remotes (local paths, `file://`, `git://`) are ignored.
+///
+/// # Arguments
+///
+/// * `remote` - URL of the git remote
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::repository::gitlab_project_from_remote;
+///
+/// assert_eq!(
+///     gitlab_project_from_remote("git@gitlab.example.com:group/subgroup/app.git"),
+///     Some(("https://gitlab.example.com".to_owned(), "group/subgroup/app".to_owned()))
+/// );
+/// ```
+///
+pub fn gitlab_project_from_remote(remote: &str) -> Option<(String, String)> {
+    let (scheme, host, path) = match remote.trim().split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+            match scheme {
+                "http" | "https" => (scheme, host, path),
+                "ssh" | "git+ssh" | "ssh+git" => ("https", host.split(':').next()?, path),
+                _ => return None,
+            }
+        }
+        None => {
+            let (authority, path) = remote.trim().split_once(':')?;
+            match authority.contains('/') || authority.len() < 2 {
+                true => return None,
+                _ => ("https", authority.rsplit_once('@').map_or(authority, |(_, host)| host), path),
+            }
+        }
+    };
+    let path = path.trim_matches('/');
+    let project = path.strip_suffix(".git").unwrap_or(path);
+    match host.is_empty() || !project.contains('/') {
+        true => None,
+        _ => Some((format!("{}://{}", scheme, host), project.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::tests::gen_alpha_char;
+    use std::env;
+
+    #[test]
+    fn test_gitlab_project_from_remote() {
+        let remotes = vec![
+            ("git@gitlab.com:group/app.git", Some(("https://gitlab.com", "group/app"))),
+            ("ssh://git@gitlab.example.com:2222/group/sub/app.git", Some(("https://gitlab.example.com", "group/sub/app"))),
+            (
+                "https://gitlab-ci-token:x@gitlab.example.com:8443/group/app",
+                Some(("https://gitlab.example.com:8443", "group/app")),
+            ),
+            ("http://localhost/group/app.git/", Some(("http://localhost", "group/app"))),
+            ("/srv/git/app.git", None),
+            ("file:///srv/git/group/app.git", None),
+            ("C:/git/group/app", None),
+            ("git@gitlab.com:app.git", None),
+        ];
+        remotes
+            .into_iter()
+            .for_each(|(remote, expected)| assert_eq!(gitlab_project_from_remote(remote), expected.map(|(u, p)| (u.to_owned(), p.to_owned())), "{}", remote));
+    }
+
+    #[test]
+    fn test_remote_url() {
+        let config = "[remote \"upstream\"]\n\turl = git@gitlab.com:upstream/app.git\n[remote \"origin\"]\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n\turl = https://gitlab.com/me/app.git\n";
+        assert_eq!(remote_url(config, "origin"), Some("https://gitlab.com/me/app.git".to_owned()));
+        assert_eq!(remote_url(config, "fork"), None);
+    }
+
+    #[test]
+    fn test_repository_defaults() {
+        let root = env::temp_dir().join(format!("repository-{}", gen_alpha_char(8)));
+        let dir = root.join("src").join("module");
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(repository_defaults(&dir), Ok(Profile::default()));
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".git").join("config"), "[remote \"origin\"]\n\turl = git@gitlab.example.com:group/app.git\n").unwrap();
+        assert_eq!(
+            repository_defaults(&dir),
+            Ok(Profile {
+                url: Some("https://gitlab.example.com".to_owned()),
+                project: Some("group/app".to_owned()),
+                ..Profile::default()
+            })
+        );
+        fs::write(root.join(REPOSITORY_CONFIG), "project = \"group/app-api\"\nenvironment = \"staging\"\n").unwrap();
+        assert_eq!(
+            repository_defaults(&dir),
+            Ok(Profile {
+                url: Some("https://gitlab.example.com".to_owned()),
+                project: Some("group/app-api".to_owned()),
+                environment: Some("staging".to_owned()),
+                ..Profile::default()
+            })
+        );
+        fs::write(root.join(REPOSITORY_CONFIG), "token = \"secret\"\n").unwrap();
+        assert!(matches!(repository_defaults(&dir), Err(InvalidInput(e)) if e.contains("is not a valid repository configuration")));
+        fs::write(root.join(REPOSITORY_CONFIG), "url = \"https://attacker.example.com\"\n").unwrap();
+        assert!(matches!(repository_defaults(&dir), Err(InvalidInput(e)) if e.contains("is not a valid repository configuration")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::api_client;
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::backup::{BackupArchive, BackupEntry, ARCHIVE_VERSION};
+use crate::config::Profile;
+use crate::dotenv::{all_from_api, RequestConfig};
+use crate::gitlab_api::{GitLabApi, GitLabVariableAttributes, VariableSource};
+use crate::io::IO;
+use crate::set_variable::write_variable;
+use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use std::fs;
+use urlencoding::encode;
+
+/// Arguments for `restore` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestoreCommand {
+    /// Path of the archive file
+    file: String,
+    /// Path of the group that replaces the original root group. By default, variables are restored in the same place.
+    to_group: Option<String>,
+    /// Parallelism
+    parallel: usize,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+impl Performable for RestoreCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            let archive = read_archive(&self.file)?;
+            let root = self.to_group.clone().unwrap_or_else(|| archive.root_group.clone());
+            let api = api_client(&self.url, &self.token);
+            let restored = restore_targets(&archive, &root)?.into_iter().try_fold(0, |restored, (entry, source)| -> Result<usize> {
+                Ok(restored + restore_entry(&self, &api, entry, &source)?)
+            })?;
+            Ok(app_success!("{} variable(s) restored under group {}", restored, root))
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for RestoreCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        RestoreCommand {
+            file: argm.value_of("FILE").unwrap().to_owned(),
+            to_group: argm.value_of("to-group").map(|v| v.to_owned()),
+            parallel: argm.value_of("parallel").and_then(|v| v.parse::<usize>().ok()).unwrap_or_else(num_cpus::get),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Reads a backup archive, checking that its format version is supported
+///
+/// # Arguments
+///
+/// * `file` - Path of the archive file
+///
+pub(crate) fn read_archive(file: &str) -> Result<BackupArchive> {
+    let content = fs::read_to_string(file).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", file, e)))?;
+    let archive = serde_json::from_str::<BackupArchive>(&content).map_err(|e| InvalidInput(format!("File {} is not a valid backup. Error: {}", file, e)))?;
+    match archive.version {
+        ARCHIVE_VERSION => Ok(archive),
+        v => Err(InvalidInput(format!("Backup version {} is not supported (expected version {})", v, ARCHIVE_VERSION))),
+    }
+}
+
+/// Returns each group and project of the archive with the place where its variables are restored. Fails before
+/// anything is restored if an entry is not under the root group of the backup.
+///
+/// # Arguments
+///
+/// * `archive` - Backup archive
+/// * `root`    - Full path of the group that replaces the original root group
+///
+fn restore_targets<'a>(archive: &'a BackupArchive, root: &str) -> Result<Vec<(&'a BackupEntry, VariableSource)>> {
+    let rebase =
+        |e: &BackupEntry| rebase_path(&e.path, &archive.root_group, root).ok_or_else(|| InvalidInput(format!("Backup entry {} is not under group {}", e.path, archive.root_group)));
+    let groups = archive.groups.iter().map(|e| Ok((e, VariableSource::Group(encode(&rebase(e)?)))));
+    let projects = archive.projects.iter().map(|e| Ok((e, VariableSource::Project(encode(&rebase(e)?)))));
+    groups.chain(projects).collect()
+}
+
+/// Creates the variables of a group or project, updating those that already exist, and returns the number of
+/// restored variables
+///
+/// # Arguments
+///
+/// * `cmd`    - Restore command
+/// * `api`    - GitLab API client
+/// * `entry`  - Variables of the group or project
+/// * `source` - Group or project where variables are restored
+///
+fn restore_entry(cmd: &RestoreCommand, api: &impl GitLabApi, entry: &BackupEntry, source: &VariableSource) -> Result<usize> {
+    app_info!("Restoring {} variable(s) of {}...", entry.variables.len(), entry.path);
+    let existing = all_from_api(RequestConfig::new(&cmd.url, &cmd.token, source.clone(), 1, 100), cmd.parallel)?;
+    entry.variables.iter().try_fold(0, |restored, v| {
+        let exists = existing.iter().any(|e| e.key == v.key && e.environment_scope == v.environment_scope);
+        write_variable(api, source, &GitLabVariableAttributes::from(v), exists).map(|_| restored + 1)
+    })
+}
+
+/// Returns `path` with its `root` group prefix replaced by `new_root`, or `None` if `path` is not under `root`
+///
+/// # Arguments
+///
+/// * `path`     - Full path of a group or project
+/// * `root`     - Full path of the original root group
+/// * `new_root` - Full path of the new root group
+///
+fn rebase_path(path: &str, root: &str, new_root: &str) -> Option<String> {
+    match path.strip_prefix(root) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => Some(format!("{}{}", new_root, rest)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+    use httpmock::MockServer;
+
+    fn gen_archive(version: u32) -> BackupArchive {
+        BackupArchive {
+            version,
+            root_group: "root".to_owned(),
+            groups: vec![BackupEntry {
+                path: "root".to_owned(),
+                variables: vec![GEN_GITLAB_VARIABLE.clone()],
+            }],
+            projects: vec![],
+        }
+    }
+
+    #[test]
+    fn test_should_create_restore_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "restore",
+                "backup.json",
+                "--to-group=new-root",
+                "--parallel=2",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("restore")
+            .map(|a| {
+                assert_eq!(
+                    RestoreCommand::from(a),
+                    RestoreCommand {
+                        file: "backup.json".to_owned(),
+                        to_group: Some("new-root".to_owned()),
+                        parallel: 2,
+                        url: "gitlab.com".to_owned(),
+                        token: GEN_TOKEN.clone(),
+                    }
+                )
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rebase_path() {
+        assert_eq!(rebase_path("root", "root", "new-root"), Some("new-root".to_owned()));
+        assert_eq!(rebase_path("root/sub/app", "root", "new/root"), Some("new/root/sub/app".to_owned()));
+        assert_eq!(rebase_path("root-2/app", "root", "new-root"), None);
+    }
+
+    #[test]
+    fn test_restore_targets_rejects_entries_outside_root() {
+        let archive = BackupArchive {
+            projects: vec![BackupEntry {
+                path: "root/app".to_owned(),
+                variables: vec![],
+            }],
+            ..gen_archive(ARCHIVE_VERSION)
+        };
+        assert_eq!(
+            restore_targets(&archive, "new-root").map(|t| t.into_iter().map(|(_, s)| s).collect::<Vec<VariableSource>>()),
+            Ok(vec![VariableSource::Group("new-root".to_owned()), VariableSource::Project(encode("new-root/app"))])
+        );
+        let archive = BackupArchive {
+            projects: vec![BackupEntry {
+                path: "other/shared-app".to_owned(),
+                variables: vec![],
+            }],
+            ..archive
+        };
+        assert!(matches!(restore_targets(&archive, "new-root"), Err(InvalidInput(e)) if e.contains("other/shared-app")));
+    }
+
+    #[test]
+    fn test_read_archive_rejects_unsupported_version() {
+        let file = format!("{}/backup-{}.json", env::temp_dir().display(), *GEN_NAME);
+        fs::write(&file, serde_json::to_string(&gen_archive(ARCHIVE_VERSION + 1)).unwrap()).unwrap();
+        assert!(matches!(read_archive(&file), Err(InvalidInput(e)) if e.starts_with(&format!("Backup version {}", ARCHIVE_VERSION + 1))));
+        fs::write(&file, serde_json::to_string(&gen_archive(ARCHIVE_VERSION)).unwrap()).unwrap();
+        assert_eq!(read_archive(&file), Ok(gen_archive(ARCHIVE_VERSION)));
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_should_restore_entry_under_new_root() {
+        let archive = gen_archive(ARCHIVE_VERSION);
+        let server = MockServer::start();
+        let list_mock = server.mock(httpmock_list_group_variables("new-root".to_owned(), vec![]));
+        let create_mock = server.mock(|when, then| {
+            when.method("POST")
+                .path("/api/v4/groups/new-root/variables")
+                .json_body_obj(&GitLabVariableAttributes::from(&*GEN_GITLAB_VARIABLE));
+            then.status(201).header("Content-Type", "application/json").json_body_obj(&*GEN_GITLAB_VARIABLE);
+        });
+        let cmd = RestoreCommand {
+            file: "backup.json".to_owned(),
+            to_group: Some("new-root".to_owned()),
+            parallel: 1,
+            url: server.base_url(),
+            token: GEN_TOKEN.clone(),
+        };
+        let source = VariableSource::Group(encode(&rebase_path(&archive.groups[0].path, &archive.root_group, "new-root").unwrap()));
+        assert_eq!(restore_entry(&cmd, &api_client(&server.base_url(), &GEN_TOKEN), &archive.groups[0], &source), Ok(1));
+        list_mock.assert();
+        create_mock.assert();
+    }
+}
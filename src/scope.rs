@@ -0,0 +1,202 @@
+use crate::api_client::DEFAULT_ENVIRONMENT;
+use crate::gitlab_api::GitLabVariable;
+use std::collections::HashMap;
+
+/// Returns `true` if a variable defined with `scope` is available in `environment`, following GitLab
+/// environment scope semantics: `*` matches any sequence of characters (including `/`) and any other
+/// character must match exactly. The "All" scope matches every environment, while the "All" environment
+/// (no environment at all) only receives variables defined for "All".
+///
+/// # Arguments
+///
+/// * `scope`       - Environment scope of a GitLab variable (e.g. `review/*`)
+/// * `environment` - Name of GitLab CI/CD environment (e.g. `review/feature-x`)
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::scope::scope_matches;
+///
+/// assert!(scope_matches("review/*", "review/feature-x"));
+/// assert!(scope_matches("All", "production"));
+/// assert!(!scope_matches("*-staging", "production"));
+/// ```
+///
+pub fn scope_matches(scope: &str, environment: &str) -> bool {
+    match (scope, environment) {
+        (DEFAULT_ENVIRONMENT, _) => true,
+        (_, DEFAULT_ENVIRONMENT) => false,
+        _ => wildcard_match(scope.as_bytes(), environment.as_bytes()),
+    }
+}
+
+/// Returns the specificity of an environment scope. When a key is defined in several scopes matching the
+/// same environment, GitLab picks an exact scope over a wildcard scope, and any of them over "All". Between
+/// scopes of the same kind, the longest one wins.
+///
+/// # Arguments
+///
+/// * `scope` - Environment scope of a GitLab variable
+///
+pub fn scope_specificity(scope: &str) -> (bool, bool, usize) {
+    (scope != DEFAULT_ENVIRONMENT, !scope.contains('*'), scope.len())
+}
+
+/// Keeps one variable per key among those available in `environment`, picking the one defined in the most
+/// specific scope. Keys keep the order in which they first appear in `variables`, and the `environment_scope`
+/// of each resolved variable is the scope its value came from.
+///
+/// # Arguments
+///
+/// * `variables`   - List of GitLab variables
+/// * `environment` - Name of GitLab CI/CD environment
+///
+pub(crate) fn resolve_variables(variables: Vec<GitLabVariable>, environment: &str) -> Vec<GitLabVariable> {
+    let (keys, mut resolved) = variables.into_iter().filter(|v| scope_matches(&v.environment_scope, environment)).fold(
+        (vec![], HashMap::new()),
+        |(mut keys, mut acc): (Vec<String>, HashMap<String, GitLabVariable>), v| {
+            match acc.get(&v.key) {
+                Some(current) if scope_specificity(&current.environment_scope) >= scope_specificity(&v.environment_scope) => (),
+                Some(_) => {
+                    acc.insert(v.key.clone(), v);
+                }
+                None => {
+                    keys.push(v.key.clone());
+                    acc.insert(v.key.clone(), v);
+                }
+            }
+            (keys, acc)
+        },
+    );
+    keys.into_iter().filter_map(|k| resolved.remove(&k)).collect()
+}
+
+/// Merges resolved lists of variables from several levels (e.g. project, group and parent group), keeping for
+/// each key the variable of the level with the highest precedence.
+///
+/// # Arguments
+///
+/// * `levels` - Lists of resolved variables, ordered from the highest to the lowest precedence
+///
+pub(crate) fn merge_variables(levels: Vec<Vec<GitLabVariable>>) -> Vec<GitLabVariable> {
+    levels.into_iter().flatten().fold(vec![], |mut acc: Vec<GitLabVariable>, v| {
+        if !acc.iter().any(|o| o.key == v.key) {
+            acc.push(v);
+        }
+        acc
+    })
+}
+
+/// Returns `true` if `text` matches `pattern`, where `*` in `pattern` matches any sequence of characters
+///
+/// # Arguments
+///
+/// * `pattern` - Pattern with optional `*` wildcards
+/// * `text`    - Text to match
+///
+pub fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t, mut star, mut backtrack) = (0, 0, None, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some(p);
+                backtrack = t;
+                p += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some(s) => {
+                    p = s + 1;
+                    backtrack += 1;
+                    t = backtrack;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab_api::tests::gen_variable;
+
+    fn gen_scoped_variable(key: &str, scope: &str) -> GitLabVariable {
+        GitLabVariable {
+            key: key.to_owned(),
+            environment_scope: scope.to_owned(),
+            ..gen_variable(None)
+        }
+    }
+
+    #[test]
+    fn test_all_scope_matches_any_environment() {
+        assert!(scope_matches(DEFAULT_ENVIRONMENT, DEFAULT_ENVIRONMENT));
+        assert!(scope_matches(DEFAULT_ENVIRONMENT, "production"));
+        assert!(scope_matches(DEFAULT_ENVIRONMENT, "review/feature-x"));
+    }
+
+    #[test]
+    fn test_all_environment_only_matches_all_scope() {
+        assert!(!scope_matches("production", DEFAULT_ENVIRONMENT));
+        assert!(!scope_matches("*l*", DEFAULT_ENVIRONMENT));
+    }
+
+    #[test]
+    fn test_exact_scope() {
+        assert!(scope_matches("production", "production"));
+        assert!(!scope_matches("production", "production-eu"));
+        assert!(!scope_matches("Production", "production"));
+    }
+
+    #[test]
+    fn test_wildcard_scope() {
+        assert!(scope_matches("review/*", "review/feature-x"));
+        assert!(scope_matches("review/*", "review/feature/nested"));
+        assert!(!scope_matches("review/*", "staging"));
+        assert!(scope_matches("*-staging", "eu-staging"));
+        assert!(!scope_matches("*-staging", "eu-staging-2"));
+        assert!(scope_matches("*/eu-*", "prod/eu-west"));
+        assert!(scope_matches("a**b", "ab"));
+    }
+
+    #[test]
+    fn test_scope_specificity() {
+        assert!(scope_specificity("production") > scope_specificity("prod*"));
+        assert!(scope_specificity("prod*") > scope_specificity("*"));
+        assert!(scope_specificity("*") > scope_specificity(DEFAULT_ENVIRONMENT));
+        assert!(scope_specificity("review/eu-*") > scope_specificity("review/*"));
+    }
+
+    #[test]
+    fn test_resolve_variables_keeps_most_specific_scope() {
+        let variables = vec![
+            gen_scoped_variable("A", DEFAULT_ENVIRONMENT),
+            gen_scoped_variable("B", "production"),
+            gen_scoped_variable("A", "production"),
+            gen_scoped_variable("A", "prod*"),
+            gen_scoped_variable("C", "staging"),
+            gen_scoped_variable("D", "prod*"),
+        ];
+        assert_eq!(
+            resolve_variables(variables.clone(), "production"),
+            vec![variables[2].clone(), variables[1].clone(), variables[5].clone()]
+        );
+        assert_eq!(resolve_variables(variables.clone(), DEFAULT_ENVIRONMENT), vec![variables[0].clone()]);
+    }
+
+    #[test]
+    fn test_merge_variables_keeps_highest_precedence_level() {
+        let project = vec![gen_scoped_variable("A", DEFAULT_ENVIRONMENT)];
+        let group = vec![gen_scoped_variable("A", "production"), gen_scoped_variable("B", "production")];
+        let parent_group = vec![gen_scoped_variable("B", DEFAULT_ENVIRONMENT), gen_scoped_variable("C", DEFAULT_ENVIRONMENT)];
+        assert_eq!(
+            merge_variables(vec![project.clone(), group.clone(), parent_group.clone()]),
+            vec![project[0].clone(), group[1].clone(), parent_group[1].clone()]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use crate::api_client::api_client;
+use crate::app_error::{
+    AppError::{InvalidInput, NotFound},
+    Result,
+};
+use crate::config::{project_or_group, Profile};
+use crate::gitlab_api::{GitLabApi, GitLabVariable, GitLabVariableAttributes, GitLabVariableType, VariableSource};
+use crate::io::IO;
+use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use std::convert::From;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use urlencoding::encode;
+
+/// Arguments for `set` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetVariableCommand {
+    /// Attributes of the variable to create or update
+    variable: GitLabVariableAttributes,
+    /// File to read the value of the variable from (`-` for STDIN), instead of the command line
+    value_file: Option<String>,
+    /// Project or group where the variable will be created or updated
+    source: VariableSource,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+impl Performable for SetVariableCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            app_info!("Setting variable {} in environment {}...", &self.variable.key, &self.variable.environment_scope);
+            read_value(self.clone()).and_then(|cmd| set_variable(&cmd)).map(|(v, created)| {
+                app_success!(
+                    "Variable {} {} successfully in scope {}{}",
+                    v.key,
+                    if created { "created" } else { "updated" },
+                    v.environment_scope,
+                    v.details()
+                )
+            })
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for SetVariableCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        SetVariableCommand {
+            variable: GitLabVariableAttributes {
+                key: argm.value_of("VARIABLE_NAME").unwrap().to_owned(),
+                value: argm.value_of("VALUE").filter(|v| *v != "-").unwrap_or_default().to_owned(),
+                environment_scope: argm.value_of("environment").unwrap().to_owned(),
+                variable_type: argm
+                    .value_of("type")
+                    .map(|t| if t == "file" { GitLabVariableType::File } else { GitLabVariableType::EnvVar }),
+                protected: bool_param_from_args(argm, "protected"),
+                masked: bool_param_from_args(argm, "masked"),
+                raw: bool_param_from_args(argm, "raw"),
+                description: argm.value_of("description").map(|v| v.to_owned()),
+            },
+            value_file: argm.value_of("value-file").or_else(|| argm.value_of("VALUE").filter(|v| *v == "-")).map(|f| f.to_owned()),
+            source: source_from_args(argm, &profile),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Returns param of type `bool` from [ArgMatches](struct@clap::ArgMatches) object, or `None` if it is not present
+///
+/// # Arguments
+///
+/// * `argm`  - Reference of [ArgMatches](ArgMatches) object
+/// * `param` - Name of parameter to extract
+///
+pub(crate) fn bool_param_from_args(argm: &ArgMatches, param: &str) -> Option<bool> {
+    argm.value_of(param).map(|v| v == "true")
+}
+
+/// Returns the project or group defined with `--project` or `--group` options, or the default project or group (see
+/// [project_or_group](fn@crate::config::project_or_group))
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@crate::config::defaults))
+///
+pub(crate) fn source_from_args(argm: &ArgMatches, profile: &Profile) -> VariableSource {
+    match project_or_group(argm, profile) {
+        (Some(p), _) => VariableSource::Project(encode(&p)),
+        (_, g) => VariableSource::Group(encode(&g.unwrap_or_default())),
+    }
+}
+
+/// Returns the command with the value of the variable read from `--value-file`, or from STDIN if the value is `-`.
+/// A single trailing newline is removed from values read from STDIN (e.g. `echo "$SECRET" | gitlab-rescue set ...`),
+/// while files are read unchanged.
+fn read_value(cmd: SetVariableCommand) -> Result<SetVariableCommand> {
+    let value = match cmd.value_file.as_deref() {
+        None => return Ok(cmd),
+        Some("-") => {
+            let mut value = String::new();
+            io::stdin()
+                .read_to_string(&mut value)
+                .map_err(|e| InvalidInput(format!("Value could not be read from STDIN. Error: {}", e)))?;
+            value.strip_suffix('\n').map(|v| v.strip_suffix('\r').unwrap_or(v).to_owned()).unwrap_or(value)
+        }
+        Some(file) => fs::read_to_string(file).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", file, e)))?,
+    };
+    Ok(SetVariableCommand {
+        variable: GitLabVariableAttributes { value, ..cmd.variable },
+        ..cmd
+    })
+}
+
+/// Returns `true` if the variable already exists in the environment scope of the project or group
+fn variable_exists(cmd: &SetVariableCommand) -> Result<bool> {
+    let api = api_client(&cmd.url, &cmd.token);
+    let (key, env) = (&cmd.variable.key, &cmd.variable.environment_scope);
+    match &cmd.source {
+        VariableSource::Project(p) => api.get_from_project(p, key, env),
+        VariableSource::Group(g) => api.get_from_group(g, key, env),
+        VariableSource::Instance => api.get_from_instance(key),
+    }
+    .map(|_| true)
+    .or_else(|e| match e {
+        NotFound(_) => Ok(false),
+        _ => Err(e),
+    })
+}
+
+/// Creates the variable, or updates it in place if it already exists in the same environment scope. Returns the
+/// resulting variable and whether it was created.
+fn set_variable(cmd: &SetVariableCommand) -> Result<(GitLabVariable, bool)> {
+    let exists = variable_exists(cmd)?;
+    Ok((write_variable(&api_client(&cmd.url, &cmd.token), &cmd.source, &cmd.variable, exists)?, !exists))
+}
+
+/// Creates a variable in a project or group, or updates it if it already exists
+///
+/// # Arguments
+///
+/// * `api`      - [GitLabApi](trait@GitLabApi) object
+/// * `source`   - Project or group where the variable will be written
+/// * `variable` - Attributes of the variable
+/// * `exists`   - Whether the variable already exists in its environment scope
+///
+pub(crate) fn write_variable(api: &impl GitLabApi, source: &VariableSource, variable: &GitLabVariableAttributes, exists: bool) -> Result<GitLabVariable> {
+    match (exists, source) {
+        (true, VariableSource::Project(p)) => api.update_in_project(p, variable),
+        (true, VariableSource::Group(g)) => api.update_in_group(g, variable),
+        (false, VariableSource::Project(p)) => api.create_in_project(p, variable),
+        (false, VariableSource::Group(g)) => api.create_in_group(g, variable),
+        (_, VariableSource::Instance) => Err(InvalidInput("Instance variables can not be written with this command".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_error::AppError;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+    use httpmock::MockServer;
+
+    fn gen_setvar_command(url: &str, source: VariableSource) -> SetVariableCommand {
+        SetVariableCommand {
+            variable: gen_variable_attributes(&GEN_GITLAB_VARIABLE),
+            value_file: None,
+            source,
+            url: url.to_owned(),
+            token: GEN_TOKEN.clone(),
+        }
+    }
+
+    fn httpmock_existing_variable(path: String, variable: Option<GitLabVariable>) -> impl FnOnce(httpmock::When, httpmock::Then) {
+        move |when, then| {
+            when.method("GET")
+                .path(path)
+                .query_param("filter[environment_scope]", GEN_GITLAB_VARIABLE.environment_scope.as_str());
+            match variable {
+                Some(v) => then.status(200).header("Content-Type", "application/json").json_body_obj(&v),
+                None => then.status(404),
+            };
+        }
+    }
+
+    #[test]
+    fn test_should_create_set_variable_cmd_from_cli_args() {
+        let cmd = gen_setvar_command("gitlab.com", VariableSource::Project(GEN_PROJECT_NAME.clone()));
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue".to_owned(),
+                "set".to_owned(),
+                GEN_GITLAB_VARIABLE.key.clone(),
+                GEN_GITLAB_VARIABLE.value.clone(),
+                format!("-e={}", GEN_GITLAB_VARIABLE.environment_scope),
+                format!("-p={}", *GEN_PROJECT_NAME),
+                format!(
+                    "--type={}",
+                    if GEN_GITLAB_VARIABLE.variable_type == GitLabVariableType::File {
+                        "file"
+                    } else {
+                        "env_var"
+                    }
+                ),
+                format!("--protected={}", cmd.variable.protected.unwrap()),
+                "-u=gitlab.com".to_owned(),
+                format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("set")
+            .map(|a| assert_eq!(SetVariableCommand::from(a), cmd))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_should_read_value_from_file() {
+        let file = env::temp_dir().join(format!("value-{}.txt", *GEN_NAME));
+        fs::write(&file, "first\nsecond\n").unwrap();
+        let cmd = SetVariableCommand {
+            value_file: Some(file.to_string_lossy().into_owned()),
+            ..gen_setvar_command("gitlab.com", VariableSource::Project(GEN_PROJECT_NAME.clone()))
+        };
+        assert_eq!(read_value(cmd).map(|c| c.variable.value), Ok("first\nsecond\n".to_owned()));
+        fs::remove_file(file).ok();
+        let cmd = SetVariableCommand {
+            value_file: Some("missing-value-file.txt".to_owned()),
+            ..gen_setvar_command("gitlab.com", VariableSource::Project(GEN_PROJECT_NAME.clone()))
+        };
+        assert!(matches!(read_value(cmd), Err(InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_should_fail_if_variable_could_not_be_checked() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET")
+                .path(format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, GEN_GITLAB_VARIABLE.key));
+            then.status(403);
+        });
+        let cmd = gen_setvar_command(&server.base_url(), VariableSource::Project(GEN_PROJECT_NAME.clone()));
+        assert!(matches!(variable_exists(&cmd), Err(AppError::Api(_))));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_create_variable_in_project() {
+        let server = MockServer::start();
+        let get_mock = server.mock(httpmock_existing_variable(
+            format!("/api/v4/projects/{}/variables/{}", *GEN_PROJECT_NAME, GEN_GITLAB_VARIABLE.key),
+            None,
+        ));
+        let cmd = gen_setvar_command(&server.base_url(), VariableSource::Project(GEN_PROJECT_NAME.clone()));
+        let mock = server.mock(httpmock_write_variable(
+            "POST",
+            format!("/api/v4/projects/{}/variables", *GEN_PROJECT_NAME),
+            GEN_GITLAB_VARIABLE.clone(),
+        ));
+        assert_eq!(set_variable(&cmd), Ok((GEN_GITLAB_VARIABLE.clone(), true)));
+        get_mock.assert();
+        mock.assert();
+    }
+
+    #[test]
+    fn test_should_update_variable_in_group() {
+        let server = MockServer::start();
+        let get_mock = server.mock(httpmock_existing_variable(
+            format!("/api/v4/groups/{}/variables/{}", *GEN_GROUP_NAME, GEN_GITLAB_VARIABLE.key),
+            Some(GEN_GITLAB_VARIABLE.clone()),
+        ));
+        let cmd = gen_setvar_command(&server.base_url(), VariableSource::Group(GEN_GROUP_NAME.clone()));
+        let mock = server.mock(httpmock_write_variable(
+            "PUT",
+            format!("/api/v4/groups/{}/variables/{}", *GEN_GROUP_NAME, GEN_GITLAB_VARIABLE.key),
+            GEN_GITLAB_VARIABLE.clone(),
+        ));
+        assert_eq!(set_variable(&cmd), Ok((GEN_GITLAB_VARIABLE.clone(), false)));
+        get_mock.assert();
+        mock.assert();
+    }
+}
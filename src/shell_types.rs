@@ -7,6 +7,12 @@ pub enum ShellType {
     Posix,
     /// Fish shell
     Fish,
+    /// PowerShell (Windows PowerShell and PowerShell Core)
+    PowerShell,
+    /// Windows Command Prompt (cmd.exe)
+    Cmd,
+    /// Nushell
+    Nushell,
 }
 
 impl Display for ShellType {
@@ -14,12 +20,31 @@ impl Display for ShellType {
         match self {
             ShellType::Posix => write!(f, "posix"),
             ShellType::Fish => write!(f, "fish"),
+            ShellType::PowerShell => write!(f, "powershell"),
+            ShellType::Cmd => write!(f, "cmd"),
+            ShellType::Nushell => write!(f, "nushell"),
+        }
+    }
+}
+
+impl From<&str> for ShellType {
+    /// Returns the shell type of a shell name as accepted by `--shell` option. Unknown names are POSIX shells.
+    fn from(name: &str) -> Self {
+        match name {
+            "fish" => ShellType::Fish,
+            "powershell" | "pwsh" => ShellType::PowerShell,
+            "cmd" => ShellType::Cmd,
+            "nushell" | "nu" => ShellType::Nushell,
+            _ => ShellType::Posix,
         }
     }
 }
 
 impl ShellType {
-    /// Generate a `export` command according to shell type
+    /// Generate a `export` command according to shell type. The value is single-quoted, so the shell does not
+    /// expand or execute anything inside it when the command is evaluated. In cmd.exe, the whole assignment is
+    /// double-quoted and `%` is doubled, so values must be checked with [can_export](fn@ShellType::can_export)
+    /// first.
     ///
     /// # Example
     ///
@@ -27,25 +52,253 @@ impl ShellType {
     /// use gitlab_rescue::shell_types::ShellType;
     ///
     /// let shell = ShellType::Posix;
-    /// let expected = "export HOME=\"/home/user\"".to_owned();
-    /// assert_eq!(shell.export_command("HOME".to_owned(), "/home/user".to_owned()), expected);
+    /// let expected = "export GREETING='it'\\''s $HOME'".to_owned();
+    /// assert_eq!(shell.export_command("GREETING".to_owned(), "it's $HOME".to_owned()), expected);
     /// ```
     ///
     pub fn export_command(&self, variable: String, value: String) -> String {
         match self {
-            ShellType::Posix => format!("export {}=\"{}\"", variable, value),
-            ShellType::Fish => format!("set -gx {} \"{}\"", variable, value),
+            ShellType::Posix => format!("export {}={}", variable, posix_quote(&value)),
+            ShellType::Fish => format!("set -gx {} {}", variable, fish_quote(&value)),
+            ShellType::PowerShell => format!("$env:{} = {}", variable, powershell_quote(&value)),
+            ShellType::Cmd => format!("set \"{}={}\"", variable, value.replace('%', "%%")),
+            ShellType::Nushell => format!("$env.{} = {}", variable, nushell_quote(&value)),
+        }
+    }
+
+    /// Returns `false` if `value` can not be exported by this shell. Variables of cmd.exe can not hold new lines,
+    /// and a double quote would end the quoted assignment, so the rest of the value would run as commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value of the variable
+    ///
+    pub fn can_export(&self, value: &str) -> bool {
+        !matches!(self, ShellType::Cmd) || !value.contains(['\n', '\r', '"'])
+    }
+
+    /// Returns the path of the file where a variable of type "File" is stored, with the path separator of the shell
+    ///
+    /// # Arguments
+    ///
+    /// * `folder`   - Folder where variables of type "File" are stored
+    /// * `variable` - Name of the variable
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use gitlab_rescue::shell_types::ShellType;
+    ///
+    /// assert_eq!(ShellType::Posix.file_path(".env.review/x", "CERT"), ".env.review/x/CERT.var");
+    /// assert_eq!(ShellType::Cmd.file_path(".env.review/x", "CERT"), ".env.review\\x\\CERT.var");
+    /// ```
+    ///
+    pub fn file_path(&self, folder: &str, variable: &str) -> String {
+        match self {
+            ShellType::PowerShell | ShellType::Cmd => format!("{}\\{}.var", folder.replace('/', "\\"), variable),
+            _ => format!("{}/{}.var", folder, variable),
         }
     }
 }
 
+/// Returns `value` quoted for POSIX shells. Nothing is special inside single quotes, so each single quote of the
+/// value closes the quoted string, is added escaped and opens a new quoted string.
+///
+/// # Arguments
+///
+/// * `value` - Value to quote
+///
+pub(crate) fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Returns `value` quoted for fish shell. Inside single quotes, fish only interprets `\\` and `\'`.
+///
+/// # Arguments
+///
+/// * `value` - Value to quote
+///
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Returns `value` quoted for PowerShell. Inside single quotes, PowerShell only interprets a doubled quote as a
+/// single quote, and it also ends strings at the typographic quotes `‘`, `’`, `‚` and `‛`, so all of them are doubled.
+///
+/// # Arguments
+///
+/// * `value` - Value to quote
+///
+fn powershell_quote(value: &str) -> String {
+    let escaped = value.chars().fold(String::new(), |mut acc, c| {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            acc.push(c);
+        }
+        acc.push(c);
+        acc
+    });
+    format!("'{}'", escaped)
+}
+
+/// Returns `value` quoted for Nushell. Double quotes are used because single-quoted strings of Nushell can not
+/// contain a single quote, so backslashes, double quotes and control characters are escaped.
+///
+/// # Arguments
+///
+/// * `value` - Value to quote
+///
+fn nushell_quote(value: &str) -> String {
+    let escaped = value.chars().fold(String::new(), |mut acc, c| {
+        match c {
+            '\\' => acc.push_str("\\\\"),
+            '"' => acc.push_str("\\\""),
+            '\n' => acc.push_str("\\n"),
+            '\r' => acc.push_str("\\r"),
+            '\t' => acc.push_str("\\t"),
+            c => acc.push(c),
+        }
+        acc
+    });
+    format!("\"{}\"", escaped)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::gen::tests::gen_bool;
+    use crate::gen::tests::{gen_bool, gen_string_from, gen_usize_from_range};
     use lazy_static::lazy_static;
+    use std::io::ErrorKind;
+    use std::process::Command;
 
     lazy_static! {
         pub static ref GEN_SHELL_TYPE: ShellType = if gen_bool() { ShellType::Posix } else { ShellType::Fish };
     }
+
+    /// Characters with a special meaning for some shell, mixed with plain characters
+    pub const SHELL_SPECIAL_CHARS: &[char] = &[
+        'a', 'Z', '0', ' ', '\t', '\n', '\'', '"', '`', '$', '\\', '!', '*', '?', '~', '#', ';', '&', '|', '<', '>', '(', ')', '{', '}', '[', ']', '%', '=', 'é', '🚀', '\u{2019}',
+    ];
+
+    pub fn gen_shell_value() -> String {
+        gen_string_from(SHELL_SPECIAL_CHARS, gen_usize_from_range(0, 30))
+    }
+
+    /// Evaluates the export command of `value` in a real shell and returns the value the shell sees. The test fails
+    /// if the shell is not installed.
+    fn round_trip(shell: ShellType, program: &str, value: &str) -> String {
+        let (flags, print): (&[&str], &str) = match shell {
+            ShellType::PowerShell => (&["-NoProfile", "-Command"], "[Console]::Out.Write($env:ROUND_TRIP)"),
+            ShellType::Nushell => (&["-c"], "print -n $env.ROUND_TRIP"),
+            _ => (&["-c"], "printf '%s' \"$ROUND_TRIP\""),
+        };
+        let script = format!("{}\n{}", shell.export_command("ROUND_TRIP".to_owned(), value.to_owned()), print);
+        match Command::new(program).args(flags).arg(&script).output() {
+            Err(e) if e.kind() == ErrorKind::NotFound => panic!("{} is required by this test but it is not installed", program),
+            res => String::from_utf8(res.unwrap().stdout).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_posix_export_command() {
+        assert_eq!(
+            ShellType::Posix.export_command("A".to_owned(), "it's \"$(rm -rf ~)\" `id`\n\\".to_owned()),
+            "export A='it'\\''s \"$(rm -rf ~)\" `id`\n\\'"
+        );
+    }
+
+    #[test]
+    fn test_fish_export_command() {
+        assert_eq!(
+            ShellType::Fish.export_command("A".to_owned(), "it's (id) $HOME \\".to_owned()),
+            "set -gx A 'it\\'s (id) $HOME \\\\'"
+        );
+    }
+
+    #[test]
+    fn test_powershell_export_command() {
+        assert_eq!(
+            ShellType::PowerShell.export_command("A".to_owned(), "it's $HOME `n \\".to_owned()),
+            "$env:A = 'it''s $HOME `n \\'"
+        );
+        assert_eq!(
+            ShellType::PowerShell.export_command("A".to_owned(), "it\u{2019}; Remove-Item -Recurse ~; \u{2018}\u{201A}\u{201B}".to_owned()),
+            "$env:A = 'it\u{2019}\u{2019}; Remove-Item -Recurse ~; \u{2018}\u{2018}\u{201A}\u{201A}\u{201B}\u{201B}'"
+        );
+    }
+
+    #[test]
+    fn test_cmd_export_command() {
+        assert_eq!(ShellType::Cmd.export_command("A".to_owned(), "100% &|<>^()'`$!".to_owned()), "set \"A=100%% &|<>^()'`$!\"");
+        assert!(ShellType::Cmd.can_export("100% &|<>^()"));
+        assert!(!ShellType::Cmd.can_export("first\nsecond"));
+        assert!(!ShellType::Cmd.can_export("100% \"&calc"));
+        assert!(ShellType::Posix.can_export("100% \"&calc"));
+        assert!(ShellType::PowerShell.can_export("first\nsecond"));
+    }
+
+    #[test]
+    fn test_nushell_export_command() {
+        assert_eq!(
+            ShellType::Nushell.export_command("A".to_owned(), "it's \"$HOME\" \\\n\t(id)".to_owned()),
+            "$env.A = \"it's \\\"$HOME\\\" \\\\\\n\\t(id)\""
+        );
+    }
+
+    #[test]
+    fn test_shell_type_from_name() {
+        assert_eq!(
+            ["bash", "zsh", "fish", "powershell", "pwsh", "cmd", "nushell", "nu"]
+                .iter()
+                .map(|n| ShellType::from(*n))
+                .collect::<Vec<ShellType>>(),
+            vec![
+                ShellType::Posix,
+                ShellType::Posix,
+                ShellType::Fish,
+                ShellType::PowerShell,
+                ShellType::PowerShell,
+                ShellType::Cmd,
+                ShellType::Nushell,
+                ShellType::Nushell
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_path() {
+        assert_eq!(ShellType::Fish.file_path(".env.review/x", "CERT"), ".env.review/x/CERT.var");
+        assert_eq!(ShellType::Nushell.file_path(".env.review/x", "CERT"), ".env.review/x/CERT.var");
+        assert_eq!(ShellType::PowerShell.file_path(".env.review/x", "CERT"), ".env.review\\x\\CERT.var");
+    }
+
+    #[test]
+    fn test_values_round_trip_through_sh() {
+        (0..50)
+            .map(|_| gen_shell_value())
+            .for_each(|value| assert_eq!(round_trip(ShellType::Posix, "sh", &value), value));
+    }
+
+    #[test]
+    #[ignore = "requires fish, run with `cargo test -- --ignored`"]
+    fn test_values_round_trip_through_fish() {
+        (0..50)
+            .map(|_| gen_shell_value())
+            .for_each(|value| assert_eq!(round_trip(ShellType::Fish, "fish", &value), value));
+    }
+
+    #[test]
+    #[ignore = "requires PowerShell (pwsh), run with `cargo test -- --ignored`"]
+    fn test_values_round_trip_through_powershell() {
+        (0..50)
+            .map(|_| gen_shell_value())
+            .for_each(|value| assert_eq!(round_trip(ShellType::PowerShell, "pwsh", &value), value));
+    }
+
+    #[test]
+    #[ignore = "requires nushell (nu), run with `cargo test -- --ignored`"]
+    fn test_values_round_trip_through_nushell() {
+        (0..50)
+            .map(|_| gen_shell_value())
+            .for_each(|value| assert_eq!(round_trip(ShellType::Nushell, "nu", &value), value));
+    }
 }
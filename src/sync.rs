@@ -0,0 +1,442 @@
+#![allow(clippy::unit_arg)]
+
+use crate::api_client::{api_client, DEFAULT_ENVIRONMENT};
+use crate::app_error::{AppError::InvalidInput, Result};
+use crate::config::Profile;
+use crate::delete_variable::source_description;
+use crate::dotenv::{all_from_api, RequestConfig};
+use crate::gitlab_api::{GitLabApi, GitLabVariable, GitLabVariableAttributes, GitLabVariableType, VariableSource};
+use crate::io::IO;
+use crate::set_variable::write_variable;
+use crate::{app_info, app_success, extract_token, extract_url, Performable};
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::convert::From;
+use std::env;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::path::Path;
+use urlencoding::encode;
+
+/// Arguments for `sync` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncCommand {
+    /// YAML or TOML manifest file
+    file: String,
+    /// Delete variables of GitLab that are not listed in the manifest
+    prune: bool,
+    /// Execute the plan instead of only printing it
+    apply: bool,
+    /// GitLab URL
+    url: String,
+    /// GitLab API Token
+    token: String,
+}
+
+/// List of variables that a project or group must have
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Project ID or NAMESPACE/PROJECT_NAME of the project. This should not be used with `group`.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Group ID or path of the group. This should not be used with `project`.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Variables of the project or group
+    #[serde(default)]
+    pub variables: Vec<ManifestVariable>,
+}
+
+/// Variable declared in a manifest. Its value is taken from exactly one of `value`, `from_env` and `from_file`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestVariable {
+    /// The key of the variable
+    pub key: String,
+    /// Variable's environment
+    #[serde(default = "default_environment_scope")]
+    pub environment_scope: String,
+    /// The type of a variable. Available types are: env_var and file
+    #[serde(default = "default_variable_type")]
+    pub variable_type: GitLabVariableType,
+    /// Whether the variable is only exported in protected branches and tags
+    #[serde(default)]
+    pub protected: bool,
+    /// Whether the variable is masked in job logs
+    #[serde(default)]
+    pub masked: bool,
+    /// Whether the variable is treated as a raw string (no variable expansion)
+    #[serde(default)]
+    pub raw: bool,
+    /// Description of the variable. If not present, the description of GitLab is kept.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Literal value
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Name of a local environment variable holding the value
+    #[serde(default)]
+    pub from_env: Option<String>,
+    /// Path of a local file holding the value, relative to the manifest
+    #[serde(default)]
+    pub from_file: Option<String>,
+}
+
+/// Environment scope of manifest variables without scope
+fn default_environment_scope() -> String {
+    "*".to_owned()
+}
+
+/// Type of manifest variables without type
+fn default_variable_type() -> GitLabVariableType {
+    GitLabVariableType::EnvVar
+}
+
+/// Change to apply to a variable in GitLab
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncAction {
+    /// Variable is declared in the manifest but does not exist in GitLab
+    Create,
+    /// Variable exists in GitLab with a different value, type or flags
+    Update,
+    /// Variable exists in GitLab but is not declared in the manifest
+    Delete,
+    /// Variable exists in GitLab as declared in the manifest
+    Unchanged,
+}
+
+impl Display for SyncAction {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            SyncAction::Create => write!(f, "create"),
+            SyncAction::Update => write!(f, "update"),
+            SyncAction::Delete => write!(f, "delete"),
+            SyncAction::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+impl Performable for SyncCommand {
+    fn get_action(self) -> IO<Result<()>> {
+        IO::unit(move || {
+            app_info!("Reading manifest {}...", self.file);
+            let manifest = read_manifest(&self.file)?;
+            let (source, desired) = (manifest_source(&manifest)?, desired_variables(&manifest, |name| env::var(name).ok())?);
+            app_info!("Getting variables from {}...", source_description(&source));
+            let remote = all_from_api(RequestConfig::new(&self.url, &self.token, source.clone(), 1, 100), num_cpus::get())?;
+            let plan = plan_sync(desired, &remote, self.prune);
+            let changes = plan
+                .iter()
+                .filter(|(action, _)| *action != SyncAction::Unchanged)
+                .collect::<Vec<&(SyncAction, GitLabVariableAttributes)>>();
+            changes.iter().for_each(|(action, v)| println!("{}\t{}\t{}", action, v.key, v.environment_scope));
+            match (changes.is_empty(), self.apply) {
+                (true, _) => Ok(app_success!("{} is up to date", source_description(&source))),
+                (_, false) => Ok(app_info!("{} change(s) planned. Run with --apply to execute them.", changes.len())),
+                _ => {
+                    let api = api_client(&self.url, &self.token);
+                    changes.iter().try_for_each(|(action, v)| {
+                        apply_action(&api, &source, *action, v).map(|_| app_info!("Variable {} in scope {}: {}", v.key, v.environment_scope, action))
+                    })?;
+                    Ok(app_success!("{} change(s) applied to {}", changes.len(), source_description(&source)))
+                }
+            }
+        })
+    }
+}
+
+impl From<&ArgMatches<'_>> for SyncCommand {
+    fn from(argm: &ArgMatches<'_>) -> Self {
+        let profile = Profile::from(argm);
+        SyncCommand {
+            file: argm.value_of("MANIFEST").unwrap().to_owned(),
+            prune: argm.is_present("prune"),
+            apply: argm.is_present("apply"),
+            url: extract_url!(argm, profile),
+            token: extract_token!(argm, profile),
+        }
+    }
+}
+
+/// Reads a manifest file. Its format is given by its extension: `.yaml`/`.yml` or `.toml`. Paths of `from_file`
+/// values are resolved from the folder of the manifest.
+///
+/// # Arguments
+///
+/// * `file` - Path of manifest file
+///
+pub(crate) fn read_manifest(file: &str) -> Result<Manifest> {
+    let content = fs::read_to_string(file).map_err(|e| InvalidInput(format!("File {} could not be read. Error: {}", file, e)))?;
+    let invalid = |e: String| InvalidInput(format!("File {} is not a valid manifest. Error: {}", file, e));
+    let manifest = match file.rsplit('.').next() {
+        Some("yaml") | Some("yml") => serde_yaml::from_str::<Manifest>(&content).map_err(|e| invalid(e.to_string())),
+        Some("toml") => toml::from_str::<Manifest>(&content).map_err(|e| invalid(e.to_string())),
+        _ => Err(InvalidInput(format!("File {} must have a .yaml, .yml or .toml extension", file))),
+    }?;
+    let folder = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    Ok(Manifest {
+        variables: manifest
+            .variables
+            .into_iter()
+            .map(|v| ManifestVariable {
+                from_file: v.from_file.map(|f| folder.join(f).to_string_lossy().into_owned()),
+                ..v
+            })
+            .collect(),
+        ..manifest
+    })
+}
+
+/// Returns the project or group declared in a manifest
+fn manifest_source(manifest: &Manifest) -> Result<VariableSource> {
+    match (&manifest.project, &manifest.group) {
+        (Some(p), None) => Ok(VariableSource::Project(encode(p))),
+        (None, Some(g)) => Ok(VariableSource::Group(encode(g))),
+        _ => Err(InvalidInput("Manifest must declare either a project or a group".to_owned())),
+    }
+}
+
+/// Returns the attributes of every variable declared in a manifest, reading their values from the environment or
+/// from files
+///
+/// # Arguments
+///
+/// * `manifest` - Manifest with declared variables
+/// * `env_var`  - Function that returns the value of an environment variable
+///
+fn desired_variables(manifest: &Manifest, env_var: fn(&str) -> Option<String>) -> Result<Vec<GitLabVariableAttributes>> {
+    manifest.variables.iter().try_fold(vec![], |mut acc: Vec<GitLabVariableAttributes>, v| {
+        let environment_scope = if v.environment_scope == "*" {
+            DEFAULT_ENVIRONMENT.to_owned()
+        } else {
+            v.environment_scope.clone()
+        };
+        if acc.iter().any(|o| o.key == v.key && o.environment_scope == environment_scope) {
+            return Err(InvalidInput(format!("Variable {} is declared twice in scope {}", v.key, v.environment_scope)));
+        }
+        acc.push(GitLabVariableAttributes {
+            key: v.key.clone(),
+            value: manifest_value(v, env_var)?,
+            environment_scope,
+            variable_type: Some(v.variable_type),
+            protected: Some(v.protected),
+            masked: Some(v.masked),
+            raw: Some(v.raw),
+            description: v.description.clone(),
+        });
+        Ok(acc)
+    })
+}
+
+/// Returns the value of a manifest variable
+///
+/// # Arguments
+///
+/// * `variable` - Variable declared in the manifest
+/// * `env_var`  - Function that returns the value of an environment variable
+///
+fn manifest_value(variable: &ManifestVariable, env_var: fn(&str) -> Option<String>) -> Result<String> {
+    match (&variable.value, &variable.from_env, &variable.from_file) {
+        (Some(value), None, None) => Ok(value.clone()),
+        (None, Some(name), None) => env_var(name).ok_or_else(|| InvalidInput(format!("Variable {}: environment variable {} is not set", variable.key, name))),
+        (None, None, Some(file)) => fs::read_to_string(file).map_err(|e| InvalidInput(format!("Variable {}: file {} could not be read. Error: {}", variable.key, file, e))),
+        _ => Err(InvalidInput(format!("Variable {} must have exactly one of value, from_env or from_file", variable.key))),
+    }
+}
+
+/// Returns the change to apply for each declared variable and, if `prune` is set, for each variable of GitLab
+/// that is not declared
+///
+/// # Arguments
+///
+/// * `desired` - Variables declared in the manifest
+/// * `remote`  - Variables defined in GitLab
+/// * `prune`   - Delete variables that are not declared
+///
+fn plan_sync(desired: Vec<GitLabVariableAttributes>, remote: &[GitLabVariable], prune: bool) -> Vec<(SyncAction, GitLabVariableAttributes)> {
+    let pruned = remote
+        .iter()
+        .filter(|r| prune && !desired.iter().any(|d| d.key == r.key && d.environment_scope == r.environment_scope))
+        .map(|r| (SyncAction::Delete, GitLabVariableAttributes::from(r)))
+        .collect::<Vec<(SyncAction, GitLabVariableAttributes)>>();
+    desired
+        .into_iter()
+        .map(|d| match remote.iter().find(|r| r.key == d.key && r.environment_scope == d.environment_scope) {
+            None => (SyncAction::Create, d),
+            Some(r)
+                if GitLabVariableAttributes::from(r)
+                    == GitLabVariableAttributes {
+                        description: d.description.clone().or_else(|| r.description.clone()),
+                        ..d.clone()
+                    } =>
+            {
+                (SyncAction::Unchanged, d)
+            }
+            _ => (SyncAction::Update, d),
+        })
+        .chain(pruned)
+        .collect()
+}
+
+/// Creates, updates or deletes a variable in GitLab
+fn apply_action(api: &impl GitLabApi, source: &VariableSource, action: SyncAction, variable: &GitLabVariableAttributes) -> Result<()> {
+    match (action, source) {
+        (SyncAction::Delete, VariableSource::Project(p)) => api.delete_from_project(p, &variable.key, &variable.environment_scope),
+        (SyncAction::Delete, VariableSource::Group(g)) => api.delete_from_group(g, &variable.key, &variable.environment_scope),
+        (SyncAction::Delete, VariableSource::Instance) => Err(InvalidInput("Instance variables can not be deleted by this tool".to_owned())),
+        (SyncAction::Unchanged, _) => Ok(()),
+        _ => write_variable(api, source, variable, action == SyncAction::Update).map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gitlab_api::tests::*;
+
+    fn write_manifest(extension: &str, content: &str) -> String {
+        let file = format!("{}/manifest-{}.{}", env::temp_dir().display(), *GEN_NAME, extension);
+        fs::write(&file, content).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_should_create_sync_cmd_from_cli_args() {
+        app()
+            .get_matches_from(vec![
+                "gitlab-rescue",
+                "sync",
+                "variables.yaml",
+                "--prune",
+                "--apply",
+                "-u=gitlab.com",
+                &format!("-t={}", *GEN_TOKEN),
+            ])
+            .subcommand_matches("sync")
+            .map(|a| {
+                assert_eq!(
+                    SyncCommand::from(a),
+                    SyncCommand {
+                        file: "variables.yaml".to_owned(),
+                        prune: true,
+                        apply: true,
+                        url: "gitlab.com".to_owned(),
+                        token: GEN_TOKEN.clone(),
+                    }
+                )
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_read_yaml_and_toml_manifests() {
+        let yaml = write_manifest(
+            "yaml",
+            "project: group/project\nvariables:\n  - key: DB_HOST\n    value: localhost\n  - key: DB_PASSWORD\n    environment_scope: production\n    masked: true\n    from_env: DB_PASSWORD\n",
+        );
+        let toml = write_manifest(
+            "toml",
+            "project = \"group/project\"\n\n[[variables]]\nkey = \"DB_HOST\"\nvalue = \"localhost\"\n\n[[variables]]\nkey = \"DB_PASSWORD\"\nenvironment_scope = \"production\"\nmasked = true\nfrom_env = \"DB_PASSWORD\"\n",
+        );
+        let manifest = read_manifest(&yaml).unwrap();
+        assert_eq!(read_manifest(&toml), Ok(manifest.clone()));
+        assert_eq!(manifest_source(&manifest), Ok(VariableSource::Project("group%2Fproject".to_owned())));
+        assert_eq!(
+            (
+                manifest.variables[0].environment_scope.as_str(),
+                manifest.variables[0].variable_type,
+                manifest.variables[1].masked
+            ),
+            ("*", GitLabVariableType::EnvVar, true)
+        );
+        assert!(matches!(read_manifest(&write_manifest("json", "{}")), Err(InvalidInput(e)) if e.ends_with("extension")));
+        assert!(matches!(read_manifest(&write_manifest("yml", "project: p\nunknown: 1\n")), Err(InvalidInput(_))));
+        [yaml, toml].iter().for_each(|f| fs::remove_file(f).unwrap());
+    }
+
+    #[test]
+    fn test_read_manifest_resolves_files_from_its_folder() {
+        let folder = env::temp_dir().join(format!("manifest-{}", *GEN_NAME));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("secret.txt"), "from-file").unwrap();
+        let file = folder.join("variables.yaml");
+        fs::write(&file, "group: a-group\nvariables:\n  - key: SECRET\n    from_file: secret.txt\n").unwrap();
+        let desired = read_manifest(file.to_str().unwrap()).and_then(|m| desired_variables(&m, |_| None));
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(desired.map(|d| d[0].value.clone()), Ok("from-file".to_owned()));
+    }
+
+    #[test]
+    fn test_desired_variables_read_values() {
+        let variable = ManifestVariable {
+            key: "A".to_owned(),
+            environment_scope: "*".to_owned(),
+            variable_type: GitLabVariableType::EnvVar,
+            protected: false,
+            masked: false,
+            raw: false,
+            description: None,
+            value: None,
+            from_env: Some("DB_PASSWORD".to_owned()),
+            from_file: None,
+        };
+        let manifest = Manifest {
+            project: None,
+            group: Some("a-group".to_owned()),
+            variables: vec![variable.clone()],
+        };
+        let env_var = |name: &str| match name {
+            "DB_PASSWORD" => Some("from-env".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            desired_variables(&manifest, env_var).map(|d| (d[0].value.clone(), d[0].environment_scope.clone())),
+            Ok(("from-env".to_owned(), DEFAULT_ENVIRONMENT.to_owned()))
+        );
+        let duplicated = Manifest {
+            variables: vec![variable.clone(), variable.clone()],
+            ..manifest.clone()
+        };
+        assert!(matches!(desired_variables(&duplicated, env_var), Err(InvalidInput(e)) if e.contains("declared twice")));
+        let ambiguous = ManifestVariable {
+            value: Some("literal".to_owned()),
+            ..variable.clone()
+        };
+        assert!(matches!(manifest_value(&ambiguous, env_var), Err(InvalidInput(e)) if e.contains("exactly one")));
+        assert!(matches!(manifest_value(&variable, |_| None), Err(InvalidInput(e)) if e.contains("is not set")));
+    }
+
+    #[test]
+    fn test_plan_sync() {
+        let (unchanged, changed, extra) = (gen_variable(None), gen_variable(None), gen_variable(None));
+        let desired = vec![
+            GitLabVariableAttributes::from(&unchanged),
+            GitLabVariableAttributes {
+                masked: Some(!changed.masked),
+                ..GitLabVariableAttributes::from(&changed)
+            },
+            GitLabVariableAttributes {
+                key: format!("NEW_{}", unchanged.key),
+                ..GitLabVariableAttributes::from(&unchanged)
+            },
+        ];
+        let remote = vec![unchanged.clone(), changed.clone(), extra.clone()];
+        let actions = |prune| {
+            plan_sync(desired.clone(), &remote, prune)
+                .into_iter()
+                .map(|(a, v)| (a, v.key))
+                .collect::<Vec<(SyncAction, String)>>()
+        };
+        assert_eq!(
+            actions(false),
+            vec![
+                (SyncAction::Unchanged, unchanged.key.clone()),
+                (SyncAction::Update, changed.key.clone()),
+                (SyncAction::Create, format!("NEW_{}", unchanged.key)),
+            ]
+        );
+        assert_eq!(actions(true).last(), Some(&(SyncAction::Delete, extra.key)));
+    }
+}
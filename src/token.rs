@@ -0,0 +1,403 @@
+use crate::api_client::DEFAULT_URL;
+use crate::app_error::{
+    AppError::{Cli, InvalidInput},
+    Result,
+};
+use crate::config::{user_config_dir, Profile};
+use crate::extract_url;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Tokens printed by token commands, so each command is run once even if the token is resolved several times
+static TOKEN_COMMANDS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Source of a GitLab API token
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenSource {
+    /// Token itself
+    Value(String),
+    /// File that holds the token
+    File(String),
+    /// Command that prints the token, run by the shell
+    Command(String),
+}
+
+impl TokenSource {
+    /// Returns the token of this source. Surrounding whitespace is removed from files and outputs of commands.
+    pub fn token(&self) -> Result<String> {
+        let token = match self {
+            TokenSource::Value(token) => token.to_owned(),
+            TokenSource::File(file) => {
+                let path = expand_home(file);
+                fs::read_to_string(&path)
+                    .map_err(|e| InvalidInput(format!("Token file {} could not be read. Error: {}", path.display(), e)))?
+                    .trim()
+                    .to_owned()
+            }
+            TokenSource::Command(command) => run_token_command(command)?,
+        };
+        match token.is_empty() {
+            true => Err(InvalidInput(format!("GitLab API token of {} is empty", self))),
+            _ => Ok(token),
+        }
+    }
+}
+
+impl std::fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TokenSource::Value(_) => write!(f, "--token option, variable or profile"),
+            TokenSource::File(file) => write!(f, "file {}", file),
+            TokenSource::Command(command) => write!(f, "command `{}`", command),
+        }
+    }
+}
+
+/// Returns the GitLab API token for the URL of command arguments, taken from the first of these sources that defines
+/// it: `--token`, `--token-file` and `--token-command` options, `GITLAB_API_TOKEN` and `GITLAB_OAUTH_TOKEN` variables,
+/// `token`, `token_env`, `token_file` and `token_command` settings of the selected profile, the `~/.netrc` entry of
+/// the GitLab host, the token of the host in glab's configuration file and `CI_JOB_TOKEN` variable of GitLab CI jobs.
+/// The header used for the token is detected by [AuthMode](enum@crate::gitlab_api::AuthMode).
+///
+/// Tokens of the profile are only used if the host of the URL is the host of the profile (gitlab.com if the profile
+/// has no URL), tokens of `GITLAB_API_TOKEN` and `GITLAB_OAUTH_TOKEN` variables if it is the host of `--url`
+/// option, `GITLAB_URL` variable or the profile, and `CI_JOB_TOKEN` if it is the host of `CI_SERVER_URL` variable.
+/// This way, a URL discovered from the repository never receives them. If no token is found, the error names the
+/// tokens that were refused and the host each of them belongs to.
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@crate::config::defaults))
+///
+pub fn resolve_token(argm: &ArgMatches, profile: &Profile) -> Result<String> {
+    let url = extract_url!(argm, profile);
+    let profile_url = profile.token_url.clone().unwrap_or_else(|| DEFAULT_URL.to_owned());
+    let env_url = argm
+        .value_of("url")
+        .map(|u| u.to_owned())
+        .or_else(|| env::var("GITLAB_URL").ok())
+        .unwrap_or_else(|| profile_url.clone());
+    let job_url = env::var("CI_SERVER_URL").unwrap_or_default();
+    let (env_host, profile_host, job_host) = (same_host(&url, &env_url), same_host(&url, &profile_url), same_host(&url, &job_url));
+    argm.value_of("token")
+        .map(|t| TokenSource::Value(t.to_owned()))
+        .or_else(|| argm.value_of("token-file").map(|f| TokenSource::File(f.to_owned())))
+        .or_else(|| argm.value_of("token-command").map(|c| TokenSource::Command(c.to_owned())))
+        .or_else(|| env_token("GITLAB_API_TOKEN").filter(|_| env_host))
+        .or_else(|| env_token("GITLAB_OAUTH_TOKEN").filter(|_| env_host))
+        .or_else(|| profile.token().filter(|_| profile_host).map(TokenSource::Value))
+        .or_else(|| profile.token_file.clone().filter(|_| profile_host).map(TokenSource::File))
+        .or_else(|| profile.token_command.clone().filter(|_| profile_host).map(TokenSource::Command))
+        .or_else(|| netrc_token(&url).map(TokenSource::Value))
+        .or_else(|| glab_token(&url).map(TokenSource::Value))
+        .or_else(|| env_token("CI_JOB_TOKEN").filter(|_| job_host))
+        .ok_or_else(|| {
+            let has_profile_token = profile.token().is_some() || profile.token_file.is_some() || profile.token_command.is_some();
+            let refused = [
+                (env_token("GITLAB_API_TOKEN").is_some(), "GITLAB_API_TOKEN variable", &env_url),
+                (env_token("GITLAB_OAUTH_TOKEN").is_some(), "GITLAB_OAUTH_TOKEN variable", &env_url),
+                (has_profile_token, "Token of the profile", &profile_url),
+                (env_token("CI_JOB_TOKEN").is_some(), "CI_JOB_TOKEN variable", &job_url),
+            ]
+            .iter()
+            .filter(|(present, _, token_url)| *present && !same_host(&url, token_url))
+            .map(|(_, name, token_url)| format!(" {} was not sent to {} because it belongs to {}.", name, host_name(&url), host_name(token_url)))
+            .collect::<String>();
+            InvalidInput(format!(
+                "No GitLab API token found for {}.{} Use --token-file or --token-command options, export GITLAB_API_TOKEN \
+                 variable (with GITLAB_URL or --url for a host other than gitlab.com), set a token in a profile, or add \
+                 the host to ~/.netrc or to glab's configuration.",
+                url, refused
+            ))
+        })
+        .and_then(|source| source.token())
+}
+
+/// Returns the host of a URL for error messages, or the URL itself if it has no host
+///
+/// # Arguments
+///
+/// * `url` - URL
+///
+fn host_name(url: &str) -> String {
+    url_host(url).map_or_else(|| format!("\"{}\"", url), |(authority, _)| authority)
+}
+
+/// Returns the value of an environment variable that holds a token, if it is not empty
+///
+/// # Arguments
+///
+/// * `variable` - Name of the variable
+///
+fn env_token(variable: &str) -> Option<TokenSource> {
+    env::var(variable).ok().filter(|t| !t.is_empty()).map(TokenSource::Value)
+}
+
+/// Checks that a GitLab API token can be resolved from command arguments (see [resolve_token](fn@resolve_token))
+///
+/// # Arguments
+///
+/// * `argm`    - Reference of [ArgMatches](ArgMatches) object of the subcommand
+/// * `profile` - Defaults of the repository and the selected profile (see [defaults](fn@crate::config::defaults))
+///
+pub fn check_token(argm: &ArgMatches, profile: &Profile) -> Result<()> {
+    resolve_token(argm, profile).map(|_| ())
+}
+
+/// Runs a token command with the shell and returns its output. Standard input and error are inherited, so commands
+/// can ask for a passphrase.
+///
+/// # Arguments
+///
+/// * `command` - Command that prints the token
+///
+fn run_token_command(command: &str) -> Result<String> {
+    let mut tokens = TOKEN_COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(token) = tokens.get(command) {
+        return Ok(token.clone());
+    }
+    #[cfg(unix)]
+    let mut shell = Command::new("sh");
+    #[cfg(unix)]
+    shell.arg("-c");
+    #[cfg(not(unix))]
+    let mut shell = Command::new("cmd");
+    #[cfg(not(unix))]
+    shell.arg("/C");
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| Cli(format!("Token command `{}` could not be run. Error: {}", command, e)))?;
+    match output.status.success() {
+        true => {
+            let token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+            tokens.insert(command.to_owned(), token.clone());
+            Ok(token)
+        }
+        _ => Err(Cli(format!("Token command `{}` failed with {}", command, output.status))),
+    }
+}
+
+/// Returns the password of the `~/.netrc` entry of the host of `url` (`$NETRC` if present, or `~/_netrc` on Windows)
+///
+/// # Arguments
+///
+/// * `url` - URL of GitLab API
+///
+fn netrc_token(url: &str) -> Option<String> {
+    let (_, host) = url_host(url)?;
+    let path = env::var_os("NETRC").map(PathBuf::from).or_else(|| {
+        let home = home_dir()?;
+        [".netrc", "_netrc"].iter().map(|f| home.join(f)).find(|f| f.exists())
+    })?;
+    netrc_password(&fs::read_to_string(path).ok()?, &host)
+}
+
+/// Returns the password of the `machine` entry of `host` in the content of a netrc file. The `default` entry is
+/// ignored, since its password is not meant for a GitLab host.
+///
+/// # Arguments
+///
+/// * `netrc` - Content of the netrc file
+/// * `host`  - Name of the host
+///
+/// # Example
+///
+/// ```rust
+/// use gitlab_rescue::token::netrc_password;
+///
+/// let netrc = "machine github.com login me password gh-token\nmachine gitlab.com login me password gl-token\n";
+/// assert_eq!(netrc_password(netrc, "gitlab.com"), Some("gl-token".to_owned()));
+/// ```
+///
+pub fn netrc_password(netrc: &str, host: &str) -> Option<String> {
+    let mut words = netrc.split_whitespace();
+    let mut machine = None;
+    while let Some(word) = words.next() {
+        match word {
+            "machine" => machine = words.next(),
+            "default" => machine = None,
+            "login" | "account" => {
+                words.next();
+            }
+            "password" => match (machine, words.next()) {
+                (Some(machine), Some(password)) if machine == host => return Some(password.to_owned()),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Hosts of glab's configuration file
+#[derive(Debug, Default, Deserialize)]
+struct GlabConfig {
+    #[serde(default)]
+    hosts: BTreeMap<String, GlabHost>,
+}
+
+/// Host of glab's configuration file
+#[derive(Debug, Default, Deserialize)]
+struct GlabHost {
+    token: Option<String>,
+}
+
+/// Returns the token of the host of `url` in glab's configuration file (`$GLAB_CONFIG_DIR/config.yml`, or
+/// `glab-cli/config.yml` inside the user's configuration folder)
+///
+/// # Arguments
+///
+/// * `url` - URL of GitLab API
+///
+fn glab_token(url: &str) -> Option<String> {
+    let folder = env::var_os("GLAB_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| user_config_dir().map(|d| d.join("glab-cli")))?;
+    glab_host_token(&fs::read_to_string(folder.join("config.yml")).ok()?, url)
+}
+
+/// Returns the token of the host of `url` in the content of glab's configuration file
+///
+/// # Arguments
+///
+/// * `config` - Content of glab's configuration file
+/// * `url`    - URL of GitLab API
+///
+fn glab_host_token(config: &str, url: &str) -> Option<String> {
+    let (authority, host) = url_host(url)?;
+    let config = serde_yaml::from_str::<GlabConfig>(config).ok()?;
+    [authority, host]
+        .iter()
+        .find_map(|h| config.hosts.get(h).and_then(|h| h.token.clone()))
+        .filter(|t| !t.is_empty())
+}
+
+/// Returns the host of a URL with and without port
+///
+/// # Arguments
+///
+/// * `url` - URL
+///
+fn url_host(url: &str) -> Option<(String, String)> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next()?;
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = authority.split(':').next()?;
+    match host.is_empty() {
+        true => None,
+        _ => Some((authority.to_owned(), host.to_owned())),
+    }
+}
+
+/// Returns `true` if both URLs have the same host
+///
+/// # Arguments
+///
+/// * `url`   - URL
+/// * `other` - Other URL
+///
+fn same_host(url: &str, other: &str) -> bool {
+    matches!((url_host(url), url_host(other)), (Some((_, host)), Some((_, other))) if host == other)
+}
+
+/// Returns the home folder of the user
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+/// Returns a path with a leading `~` replaced by the home folder of the user
+///
+/// # Arguments
+///
+/// * `path` - Path
+///
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap_app::app;
+    use crate::gen::tests::gen_alpha_char;
+
+    fn token_from_args(args: Vec<&str>) -> Result<String> {
+        let matches = app().get_matches_from([vec!["gitlab-rescue", "get", "A", "-p", "a-project"], args].concat());
+        resolve_token(matches.subcommand_matches("get").unwrap(), &Profile::default())
+    }
+
+    #[test]
+    fn test_netrc_password() {
+        let netrc = "default login anonymous password default-token\nmachine gitlab.example.com\n  login me\n  password host-token\n";
+        assert_eq!(netrc_password(netrc, "gitlab.example.com"), Some("host-token".to_owned()));
+        assert_eq!(netrc_password(netrc, "gitlab.com"), None);
+        assert_eq!(netrc_password("machine gitlab.com login password", "gitlab.com"), None);
+    }
+
+    #[test]
+    fn test_glab_host_token() {
+        let config = "git_protocol: ssh\nhosts:\n  gitlab.com:\n    token: gl-token\n    api_host: gitlab.com\n  gitlab.example.com:8443:\n    token: self-managed-token\n";
+        assert_eq!(glab_host_token(config, "https://gitlab.com"), Some("gl-token".to_owned()));
+        assert_eq!(glab_host_token(config, "https://gitlab.example.com:8443/"), Some("self-managed-token".to_owned()));
+        assert_eq!(glab_host_token(config, "https://gitlab.example.com"), None);
+    }
+
+    #[test]
+    fn test_url_host() {
+        assert_eq!(
+            url_host("https://user@gitlab.example.com:8443/api"),
+            Some(("gitlab.example.com:8443".to_owned(), "gitlab.example.com".to_owned()))
+        );
+        assert_eq!(url_host("https:///api"), None);
+    }
+
+    #[test]
+    fn test_same_host() {
+        assert!(same_host("https://gitlab.example.com/api", "gitlab.example.com"));
+        assert!(!same_host("https://gitlab.example.com.attacker.com", "https://gitlab.example.com"));
+        assert!(!same_host("https:///api", "https:///api"));
+    }
+
+    #[test]
+    fn test_should_not_send_profile_token_to_another_host() {
+        let matches = app().get_matches_from(vec!["gitlab-rescue", "get", "A", "-p", "a-project"]);
+        let profile = Profile {
+            url: Some("https://gitlab.other.com".to_owned()),
+            token: Some("profile-token".to_owned()),
+            token_url: Some("https://gitlab.example.com".to_owned()),
+            ..Profile::default()
+        };
+        assert_ne!(resolve_token(matches.subcommand_matches("get").unwrap(), &profile), Ok("profile-token".to_owned()));
+    }
+
+    #[test]
+    fn test_token_from_file() {
+        let file = env::temp_dir().join(format!("token-{}", gen_alpha_char(8)));
+        fs::write(&file, "file-token\n").unwrap();
+        assert_eq!(token_from_args(vec!["--token-file", &file.to_string_lossy()]), Ok("file-token".to_owned()));
+        fs::write(&file, "\n").unwrap();
+        assert!(matches!(token_from_args(vec!["--token-file", &file.to_string_lossy()]), Err(InvalidInput(e)) if e.contains("is empty")));
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(token_from_args(vec!["--token-file", &file.to_string_lossy()]), Err(InvalidInput(e)) if e.contains("could not be read")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_token_from_command() {
+        assert_eq!(token_from_args(vec!["--token-command", "echo ' command-token '"]), Ok("command-token".to_owned()));
+        assert!(matches!(token_from_args(vec!["--token-command", "exit 3"]), Err(Cli(e)) if e.contains("failed")));
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::cargo::CommandCargoExt;
 use gitlab_rescue::shell_types::ShellType;
 use httpmock::{MockServer, Then, When};
@@ -47,7 +49,7 @@ fn test_should_get_a_variable_from_a_group() {
 fn httpmock_list() -> impl FnOnce(When, Then) {
     |when, then| {
         when.method("GET").path("/api/v4/projects/a-project/variables");
-        then.status(200).header("x-total", "8").body_from_file("tests/resources/response_list_variables.json");
+        then.status(200).header("x-total", "10").body_from_file("tests/resources/response_list_variables.json");
     }
 }
 
@@ -74,6 +76,7 @@ fn test_should_generate_dotenv_with_env(env: &str, shell: &str, folder: &str) {
         fs::read_to_string(format!("tests/output-{}-{}.txt", env, shell)).unwrap(),
         fs::read_to_string(format!("tests/resources/dotenv_{}_with_{}_env.txt", shell, env)).unwrap()
     );
+    fs::remove_file(format!("tests/output-{}-{}.txt", env, shell)).ok();
     mock.assert_hits(2);
 }
 
@@ -137,6 +140,306 @@ fn test_should_generate_fish_dotenv_with_prod_env() {
     assert_eq!(fs::read_to_string(format!("tests/{}/TEST_VARIABLE_7.var", path)).unwrap(), "{\"test_variable\":\"seven\"}");
 }
 
+#[test]
+fn test_should_generate_posix_dotenv_with_wildcard_env() {
+    let server = MockServer::start();
+    let mock = server.mock(httpmock_list());
+    let path = &format!(".env.review.{}", ShellType::Posix);
+    gitlab_rescue()
+        .args(&["dotenv", "a-project", "-t", "a-token", "-u", &server.base_url(), "-e", "review/feature-x"])
+        .args(&["--folder", path])
+        .assert()
+        .success()
+        .stdout(fs::read_to_string("tests/resources/dotenv_posix_with_review_env.txt").unwrap());
+    fs::remove_dir_all(format!("tests/{}", path)).ok();
+    mock.assert();
+}
+
+#[test]
+fn test_should_not_create_files_of_variables_for_docker_and_direnv() {
+    let server = MockServer::start();
+    let mock = server.mock(httpmock_list());
+    let output = gitlab_rescue()
+        .args(&["dotenv", "a-project", "-t", "a-token", "-u", &server.base_url(), "-e", "prod", "--format", "docker"])
+        .args(&["--folder", ".env.docker", "--container-folder", "/run/secrets"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(String::from_utf8(output).unwrap().contains("TEST_VARIABLE_7=/run/secrets/TEST_VARIABLE_7.var"));
+    assert!(!std::path::Path::new("tests/.env.docker").exists());
+    mock.assert_hits(1);
+    gitlab_rescue()
+        .args(&["dotenv", "a-project", "-t", "a-token", "-u", &server.base_url(), "-e", "prod", "--format", "direnv"])
+        .args(&["--folder", ".env.direnv"])
+        .assert()
+        .success();
+    assert!(!std::path::Path::new("tests/.env.direnv").exists());
+    mock.assert_hits(1);
+}
+
+#[test]
+fn test_should_set_a_variable_read_from_stdin() {
+    let server = MockServer::start();
+    let exists = server.mock(|when, then| {
+        when.method("GET").path("/api/v4/projects/a-project/variables/NEW_VARIABLE");
+        then.status(404);
+    });
+    let create = server.mock(|when, then| {
+        when.method("POST").path("/api/v4/projects/a-project/variables").body_contains("\"value\":\"a secret\"");
+        then.status(201).body_from_file("tests/resources/response_show_env_var_envAll.json");
+    });
+    gitlab_rescue()
+        .args(&["set", "NEW_VARIABLE", "-", "-p", "a-project", "-t", "a-token", "-u", &server.base_url()])
+        .write_stdin("a secret\n")
+        .assert()
+        .success();
+    exists.assert();
+    create.assert();
+}
+
+#[test]
+fn test_should_plan_import_of_a_dotenv_file() {
+    let server = MockServer::start();
+    let mock = server.mock(httpmock_list());
+    gitlab_rescue()
+        .args(&[
+            "import",
+            "resources/import_env.txt",
+            "-p",
+            "a-project",
+            "-t",
+            "a-token",
+            "-u",
+            &server.base_url(),
+            "--dry-run",
+        ])
+        .assert()
+        .success()
+        .stdout("unchanged\tTEST_VARIABLE_3\tAll\nupdate\tTEST_VARIABLE_6\tAll\ncreate\tNEW_VARIABLE\tAll\n");
+    mock.assert();
+}
+
+#[test]
+fn test_should_diff_project_variables_with_a_dotenv_file() {
+    let server = MockServer::start();
+    let mock = server.mock(httpmock_list());
+    gitlab_rescue()
+        .args(&["diff", "a-project", "resources/import_env.txt", "-t", "a-token", "-u", &server.base_url()])
+        .assert()
+        .success()
+        .stdout("+ NEW_VARIABLE\n- TEST_VARIABLE_4\n~ TEST_VARIABLE_6\n");
+    gitlab_rescue()
+        .args(&["diff", "a-project", "resources/import_env.txt", "-t", "a-token", "-u", &server.base_url(), "--show-values"])
+        .assert()
+        .success()
+        .stdout("+ NEW_VARIABLE=\"new\"\n- TEST_VARIABLE_4=\"{\\\"test_variable\\\":\\\"four\\\"}\"\n~ TEST_VARIABLE_6: \"TEST_6\" -> \"TEST_6_LOCAL\"\n");
+    let config = std::env::temp_dir().join(format!("gitlab-rescue-diff-{}.toml", std::process::id()));
+    fs::write(&config, "[profiles.test]\nproject = \"a-project\"\n").unwrap();
+    gitlab_rescue()
+        .env("GITLAB_RESCUE_CONFIG", &config)
+        .args(&["diff", "resources/import_env.txt", "--profile", "test", "-t", "a-token", "-u", &server.base_url()])
+        .assert()
+        .success()
+        .stdout("+ NEW_VARIABLE\n- TEST_VARIABLE_4\n~ TEST_VARIABLE_6\n");
+    fs::remove_file(&config).unwrap();
+    mock.assert_hits(3);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_should_exec_a_command_with_project_variables() {
+    let server = MockServer::start();
+    let mock = server.mock(httpmock_list());
+    let output = gitlab_rescue_command()
+        .args(&["exec", "-p", "a-project", "-t", "a-token", "-u", &server.base_url(), "-e", "prod", "--"])
+        .args(&[
+            "sh",
+            "-c",
+            "printf '%s\\n%s\\n' \"$TEST_VARIABLE_6\" \"$TEST_VARIABLE_7\" && cat \"$TEST_VARIABLE_7\" && exit 3",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<&str>>();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!((lines[0], lines[2]), ("TEST_6_PROD", "{\"test_variable\":\"seven\"}"));
+    assert!(lines[1].ends_with("/TEST_VARIABLE_7.var"));
+    assert!(!std::path::Path::new(lines[1]).exists());
+    mock.assert();
+}
+
+#[test]
+fn test_should_take_defaults_from_a_profile() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v4/projects/a-project/variables/TEST_VARIABLE_1")
+            .header("PRIVATE-TOKEN", "a-token");
+        then.status(200).body_from_file("tests/resources/response_show_env_var_envAll.json");
+    });
+    let config = std::env::temp_dir().join(format!("gitlab-rescue-{}.toml", std::process::id()));
+    fs::write(
+        &config,
+        format!("[profiles.test]\nurl = \"{}\"\ntoken = \"a-token\"\nproject = \"a-project\"\n", server.base_url()),
+    )
+    .unwrap();
+    gitlab_rescue()
+        .env("GITLAB_RESCUE_CONFIG", &config)
+        .env_remove("GITLAB_URL")
+        .env_remove("GITLAB_API_TOKEN")
+        .args(&["get", "TEST_VARIABLE_1", "--profile", "test"])
+        .assert()
+        .success()
+        .stdout("TEST_1\n");
+    let errors = [
+        (vec!["get", "TEST_VARIABLE_1", "--profile", "missing"], "Profile missing not found"),
+        (vec!["dotenv"], "A project is required by dotenv command"),
+    ];
+    errors.iter().for_each(|(args, error)| {
+        let output = gitlab_rescue_command().env("GITLAB_RESCUE_CONFIG", &config).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr).unwrap().contains(error));
+    });
+    fs::remove_file(&config).unwrap();
+    mock.assert();
+}
+
+#[test]
+fn test_should_take_the_project_from_the_git_remote() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET").path("/api/v4/projects/group%2Fa-project/variables/TEST_VARIABLE_1");
+        then.status(200).body_from_file("tests/resources/response_show_env_var_envAll.json");
+    });
+    let repository = std::env::temp_dir().join(format!("gitlab-rescue-repository-{}", std::process::id()));
+    fs::create_dir_all(repository.join(".git")).unwrap();
+    fs::write(
+        repository.join(".git").join("config"),
+        format!("[remote \"origin\"]\n\turl = {}/group/a-project.git\n", server.base_url()),
+    )
+    .unwrap();
+    gitlab_rescue()
+        .current_dir(&repository)
+        .env_remove("GITLAB_URL")
+        .args(&["get", "TEST_VARIABLE_1", "-t", "a-token"])
+        .assert()
+        .success()
+        .stdout("TEST_1\n");
+    fs::write(repository.join(".gitlab-rescue.toml"), "project = \"another-project\"\n").unwrap();
+    let output = gitlab_rescue_command()
+        .current_dir(&repository)
+        .env_remove("GITLAB_URL")
+        .args(&["get", "TEST_VARIABLE_1", "-t", "a-token"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(&repository).unwrap();
+    mock.assert();
+}
+
+#[test]
+fn test_should_take_the_token_from_netrc() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v4/projects/a-project/variables/TEST_VARIABLE_1")
+            .header("PRIVATE-TOKEN", "netrc-token");
+        then.status(200).body_from_file("tests/resources/response_show_env_var_envAll.json");
+    });
+    let home = std::env::temp_dir().join(format!("gitlab-rescue-home-{}", std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+    let run = || {
+        gitlab_rescue_command()
+            .env("HOME", &home)
+            .env_remove("GITLAB_API_TOKEN")
+            .env_remove("GITLAB_OAUTH_TOKEN")
+            .env_remove("CI_JOB_TOKEN")
+            .env_remove("GITLAB_RESCUE_CONFIG")
+            .env_remove("GITLAB_RESCUE_PROFILE")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("GLAB_CONFIG_DIR")
+            .env_remove("NETRC")
+            .args(&["get", "TEST_VARIABLE_1", "-p", "a-project", "-u", &server.base_url()])
+            .output()
+            .unwrap()
+    };
+    let output = run();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("No GitLab API token found"));
+    fs::write(home.join(".netrc"), "machine 127.0.0.1\n  login gitlab\n  password netrc-token\n").unwrap();
+    let output = run();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "TEST_1\n");
+    fs::remove_dir_all(&home).unwrap();
+    mock.assert();
+}
+
+#[test]
+fn test_should_authenticate_with_the_ci_job_token() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET")
+            .path("/api/v4/projects/a-project/variables/TEST_VARIABLE_1")
+            .header("JOB-TOKEN", "a-job-token");
+        then.status(200).body_from_file("tests/resources/response_show_env_var_envAll.json");
+    });
+    let run = |server_url: &str| {
+        gitlab_rescue_command()
+            .env("HOME", std::env::temp_dir())
+            .env("CI_JOB_TOKEN", "a-job-token")
+            .env("CI_SERVER_URL", server_url)
+            .env_remove("GITLAB_API_TOKEN")
+            .env_remove("GITLAB_OAUTH_TOKEN")
+            .env_remove("GITLAB_RESCUE_CONFIG")
+            .env_remove("GITLAB_RESCUE_PROFILE")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("GLAB_CONFIG_DIR")
+            .env_remove("NETRC")
+            .args(&["get", "TEST_VARIABLE_1", "-p", "a-project", "-u", &server.base_url()])
+            .output()
+            .unwrap()
+    };
+    assert_eq!(String::from_utf8(run(&server.base_url()).stdout).unwrap(), "TEST_1\n");
+    mock.assert();
+    let output = run("https://gitlab.example.com");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("CI_JOB_TOKEN variable was not sent to 127.0.0.1"));
+    mock.assert_hits(1);
+}
+
+#[test]
+fn test_should_name_the_host_of_refused_tokens() {
+    let server = MockServer::start();
+    let repository = std::env::temp_dir().join(format!("gitlab-rescue-refused-{}", std::process::id()));
+    fs::create_dir_all(repository.join(".git")).unwrap();
+    fs::write(
+        repository.join(".git").join("config"),
+        format!("[remote \"origin\"]\n\turl = {}/group/a-project.git\n", server.base_url()),
+    )
+    .unwrap();
+    let output = gitlab_rescue_command()
+        .current_dir(&repository)
+        .env("HOME", &repository)
+        .env("GITLAB_API_TOKEN", "an-api-token")
+        .env_remove("GITLAB_URL")
+        .env_remove("GITLAB_OAUTH_TOKEN")
+        .env_remove("CI_JOB_TOKEN")
+        .env_remove("GITLAB_RESCUE_CONFIG")
+        .env_remove("GITLAB_RESCUE_PROFILE")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("GLAB_CONFIG_DIR")
+        .env_remove("NETRC")
+        .args(&["get", "TEST_VARIABLE_1"])
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&repository).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains(&format!("GITLAB_API_TOKEN variable was not sent to {} because it belongs to gitlab.com", server.address())));
+}
+
 #[test]
 fn test_should_response_an_error() {
     gitlab_rescue()